@@ -0,0 +1,145 @@
+use std::{fmt, io, path::PathBuf};
+
+use crate::{
+    features::extensions::ManifestDiagnostic,
+    protocol::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+    results::ExtensionError,
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io(io::Error),
+
+    /// A file needed by the api doesn't exist
+    MissingFile(PathBuf),
+
+    /// A binary file couldn't be encoded or decoded
+    Bincode(bincode::Error),
+
+    /// A json message or file couldn't be encoded or decoded
+    Json(serde_json::Error),
+
+    /// A message was written with a protocol version this version of the crate can't decode
+    UnsupportedProtocol(u32),
+
+    /// No extension with the id is installed
+    ExtensionNotFound(String),
+
+    /// An extension with the id is already installed
+    ExtensionExists(String),
+
+    /// The manifest of the extension being installed has errors
+    InvalidManifest(Vec<ManifestDiagnostic>),
+
+    /// The value can't be stored in the extension setting
+    InvalidSetting { setting_id: String, message: String },
+
+    /// The setting isn't declared in the extension manifest
+    UnknownSetting(String),
+
+    /// The secrets couldn't be read or written
+    SecretStore(String),
+
+    /// The file isn't a directory, a `.tar.gz` or a `.zip` archive
+    UnsupportedArchive(PathBuf),
+
+    /// The extension exited without sending a response
+    NoResponse,
+
+    /// The extension reported that it failed
+    Extension(ExtensionError),
+}
+
+impl Error {
+    /// Turns a failed file read into [`Error::MissingFile`] when the file doesn't exist
+    pub(crate) fn from_read(error: io::Error, path: impl Into<PathBuf>) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => Error::MissingFile(path.into()),
+            _ => Error::Io(error),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::MissingFile(path) => write!(f, "File not found: {}", path.display()),
+            Error::Bincode(error) => write!(f, "Invalid binary data: {error}"),
+            Error::Json(error) => write!(f, "Invalid json: {error}"),
+            Error::UnsupportedProtocol(version) => write!(
+                f,
+                "Protocol version {version} is not supported. Supported versions are {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}"
+            ),
+            Error::ExtensionNotFound(id) => write!(f, "Extension not found: {id}"),
+            Error::ExtensionExists(id) => write!(f, "Extension already installed: {id}"),
+            Error::InvalidManifest(diagnostics) => {
+                write!(f, "Invalid manifest")?;
+
+                for diagnostic in diagnostics {
+                    write!(f, "\n{diagnostic}")?;
+                }
+
+                Ok(())
+            }
+            Error::InvalidSetting {
+                setting_id,
+                message,
+            } => write!(f, "Invalid value for setting {setting_id}: {message}"),
+            Error::UnknownSetting(setting_id) => write!(f, "Unknown setting: {setting_id}"),
+            Error::SecretStore(message) => write!(f, "{message}"),
+            Error::UnsupportedArchive(path) => {
+                write!(f, "Unsupported extension source: {}", path.display())
+            }
+            Error::NoResponse => write!(f, "The extension didn't send a response"),
+            Error::Extension(error) => match &error.details {
+                Some(details) => write!(f, "{}: {details}", error.message),
+                None => write!(f, "{}", error.message),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Bincode(error) => Some(error),
+            Error::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    fn from(error: bincode::Error) -> Self {
+        Error::Bincode(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(error: zip::result::ZipError) -> Self {
+        Error::Io(error.into())
+    }
+}
+
+impl From<ExtensionError> for Error {
+    fn from(error: ExtensionError) -> Self {
+        Error::Extension(error)
+    }
+}
@@ -6,11 +6,15 @@ use {
     tux_icons::icon_fetcher::IconFetcher,
 };
 
-use crate::paths::get_recent_apps_path;
 #[cfg(target_os = "windows")]
 use crate::paths::{get_app_dir, get_app_resources_dir};
+use crate::{
+    paths::get_recent_apps_path,
+    utils::{lock_file, write_file_atomic},
+};
 
 use {
+    crate::error::{Error, Result},
     crate::paths::{get_indexing_apps_path, get_indexing_dir},
     serde::{Deserialize, Serialize},
     std::fs,
@@ -42,6 +46,9 @@ impl App {
 
 /// Gets the apps from the system and indexes them into a file
 pub fn index_apps() {
+    // Keeps the launcher and the companion from indexing at the same time
+    let _lock = lock_file(get_indexing_apps_path()).expect("Error locking apps index");
+
     #[cfg(target_os = "linux")]
     if cfg!(target_os = "linux") {
         let mut apps_indexing = Vec::<App>::new();
@@ -80,34 +87,31 @@ pub fn index_apps() {
 
                             let split_command: Vec<&str> = id_command_out.split(" ").collect();
 
-                            let id = split_command.get(0).unwrap().to_owned().to_owned();
+                            let id = split_command.first().unwrap().to_owned().to_owned();
 
                             if !id.trim().is_empty() {
                                 let mut app_indexing = App::new(&id, &title, &exec_path);
 
-                                match icon.clone() {
-                                    Some(path) => {
-                                        let icon_path_str =
-                                            path.to_owned().into_os_string().into_string().unwrap();
+                                if let Some(path) = icon.clone() {
+                                    let icon_path_str =
+                                        path.to_owned().into_os_string().into_string().unwrap();
 
-                                        if !icon_path_str.ends_with(".svgz") {
-                                            let file_type = path
-                                                .extension()
-                                                .unwrap()
-                                                .to_os_string()
-                                                .into_string()
-                                                .unwrap();
+                                    if !icon_path_str.ends_with(".svgz") {
+                                        let file_type = path
+                                            .extension()
+                                            .unwrap()
+                                            .to_os_string()
+                                            .into_string()
+                                            .unwrap();
 
-                                            let mut index_icon_path = icons_dir.to_owned();
-                                            index_icon_path.push(format!("{id}.{file_type}"));
+                                        let mut index_icon_path = icons_dir.to_owned();
+                                        index_icon_path.push(format!("{id}.{file_type}"));
 
-                                            fs::copy(&path, &index_icon_path)
-                                                .expect("Error copying icon");
+                                        fs::copy(&path, &index_icon_path)
+                                            .expect("Error copying icon");
 
-                                            app_indexing = app_indexing.set_icon(icon_path_str);
-                                        }
+                                        app_indexing = app_indexing.set_icon(icon_path_str);
                                     }
-                                    None => {}
                                 }
 
                                 apps_indexing.push(app_indexing);
@@ -129,7 +133,7 @@ pub fn index_apps() {
         let encoded_apps_indexing =
             bincode::serialize(&apps_indexing).expect("Error serializing apps indexing");
 
-        fs::write(get_indexing_apps_path(), encoded_apps_indexing)
+        write_file_atomic(get_indexing_apps_path(), encoded_apps_indexing)
             .expect("Error writing apps indexing");
 
         refresh_recent_apps(&apps_indexing);
@@ -158,14 +162,14 @@ pub fn index_apps() {
             fs::create_dir_all(get_indexing_dir()).expect("Error creating index directory");
         }
 
-        fs::write(get_indexing_apps_path(), &bytes).expect("Error writing apps binary");
+        write_file_atomic(get_indexing_apps_path(), &bytes).expect("Error writing apps binary");
 
         refresh_recent_apps(&apps);
     }
 }
 
-fn refresh_recent_apps(apps: &Vec<App>) {
-    let recent_apps: Vec<App> = match fs::read(&get_recent_apps_path()) {
+fn refresh_recent_apps(apps: &[App]) {
+    let recent_apps: Vec<App> = match fs::read(get_recent_apps_path()) {
         Ok(bytes) => bincode::deserialize(&bytes).unwrap_or(Vec::new()),
         Err(_) => Vec::new(),
     };
@@ -178,7 +182,7 @@ fn refresh_recent_apps(apps: &Vec<App>) {
         }
     }
 
-    fs::write(
+    write_file_atomic(
         get_recent_apps_path(),
         bincode::serialize(&refreshed_recent_apps).expect("Error serializing recent apps"),
     )
@@ -186,10 +190,16 @@ fn refresh_recent_apps(apps: &Vec<App>) {
 }
 
 pub fn get_apps() -> Vec<App> {
-    let bytes = fs::read(get_indexing_apps_path()).expect("Error reading indexing apps");
-
-    match bincode::deserialize(&bytes) {
-        Ok(apps_indexing) => apps_indexing,
-        Err(_) => Vec::new(),
+    match try_get_apps() {
+        Ok(apps) => apps,
+        Err(Error::Bincode(_)) => Vec::new(),
+        Err(error) => panic!("Error reading indexing apps: {error}"),
     }
 }
+
+pub fn try_get_apps() -> Result<Vec<App>> {
+    let path = get_indexing_apps_path();
+    let bytes = fs::read(&path).map_err(|error| Error::from_read(error, path))?;
+
+    Ok(bincode::deserialize(&bytes)?)
+}
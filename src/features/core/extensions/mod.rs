@@ -1,29 +1,75 @@
-use std::fs::{self};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
 
 #[cfg(target_os = "windows")]
-use {std::fs::File, std::io::Write};
+use std::io::Write;
+
+#[cfg(unix)]
+use {
+    crate::{
+        paths::get_extension_daemon_socket_path, protocol::encode_json, transport::SocketTransport,
+    },
+    std::os::unix::net::UnixStream,
+};
 
+use flate2::read::GzDecoder;
+use serde_json::{Map, Value};
 use walkdir::WalkDir;
 
 use crate::{
-    features::extensions::{ExtensionManifest, ExtensionRequest, FormResponse},
+    error::{Error, Result},
+    features::extensions::{
+        get_current_request_id, get_daemon_request, get_extension_dir, new_request_id,
+        DiagnosticSeverity, ExtensionIndexEntry, ExtensionManifest, ExtensionManifestSetting,
+        ExtensionManifestSettingType, ExtensionManifestShowCondition, ExtensionMode,
+        ExtensionRequest, FormResponse, IndexReport, ManifestDiagnostic, ShowConditionOperator,
+    },
     paths::{
-        get_extension_request_path, get_extension_response_path, get_extensions_dir,
-        get_form_request_path, get_indexing_extensions_path,
+        get_cancelled_requests_dir, get_extension_request_path, get_extension_requests_dir,
+        get_extension_response_path, get_extension_response_path_for, get_extensions_dir,
+        get_extensions_staging_dir, get_form_request_path, get_form_responses_dir,
+        get_indexing_extension_diagnostics_path, get_indexing_extensions_path,
+    },
+    protocol::{
+        check_protocol_version, decode_message, decode_versioned_json, encode_message,
+        ExtensionRequestV1, FormResponseV1, MessageEncoding, SearchResultsV1, PROTOCOL_VERSION,
     },
-    results::OpenFormAction,
+    results::{OpenFormAction, SearchResults, SearchResultsMessage},
+    transport::{negotiate_transport, Transport, TransportKind},
+    utils::{lock_file, parse_version, write_file_atomic, CORE_VERSION},
 };
 
-use super::settings::{get_settings, write_settings, ExtensionSetting};
+use super::{
+    secrets::{delete_extension_secrets, set_secret},
+    settings::{update_settings, ExtensionSetting, Settings},
+};
+
+/// How long the launcher waits for extensions that don't set a timeout in their manifest
+pub const DEFAULT_EXTENSION_TIMEOUT: Duration = Duration::from_secs(10);
 
-pub fn index_extensions() {
+/// Indexes the extensions with a valid manifest and records the problems found in every manifest.
+///
+/// Only `<extensions dir>/<extension>/manifest.json` files are read, and manifests that didn't change since the last
+/// indexing are reused. See [`get_extension_diagnostics`].
+pub fn index_extensions() -> IndexReport {
+    // Keeps the launcher and the companion from indexing at the same time
+    let _lock = lock_file(get_indexing_extensions_path()).expect("Error locking extensions index");
+    let previous_entries = try_get_extension_index().unwrap_or_default();
+    let previous_diagnostics = get_extension_diagnostics();
     let mut extensions = Vec::<ExtensionManifest>::new();
+    let mut entries = Vec::<ExtensionIndexEntry>::new();
+    let mut diagnostics = Vec::<ManifestDiagnostic>::new();
     let extensions_dir = get_extensions_dir();
     let indexing_extensions_path = get_indexing_extensions_path();
-    let mut settings = get_settings();
 
     if !indexing_extensions_path.parent().unwrap().exists() {
-        fs::create_dir_all(&indexing_extensions_path.parent().unwrap())
+        fs::create_dir_all(indexing_extensions_path.parent().unwrap())
             .expect("Error creating directory");
     }
 
@@ -31,109 +77,1119 @@ pub fn index_extensions() {
         fs::create_dir_all(&extensions_dir).expect("Error creating extensions directory");
     }
 
-    for entry in WalkDir::new(&extensions_dir) {
-        if entry.is_ok() {
-            let entry = entry.unwrap();
-            let name = entry.file_name();
-
-            if name == "manifest.json" {
-                let json =
-                    fs::read_to_string(entry.path()).expect("Error getting manifest content");
+    for manifest_path in get_manifest_paths() {
+        let modified = get_modified_time(&manifest_path);
 
-                if let Ok(extension) = serde_json::from_str::<ExtensionManifest>(&json) {
-                    extensions.push(extension.to_owned());
+        let unchanged_entry = previous_entries.iter().find(|entry| {
+            entry.manifest_path == manifest_path && modified.is_some() && entry.modified == modified
+        });
 
-                    let has_keyword = settings
-                        .extensions
+        let manifest = match unchanged_entry {
+            Some(entry) => {
+                diagnostics.extend(
+                    previous_diagnostics
                         .iter()
-                        .any(|es| es.extension_id == extension.id && es.setting_id == "keyword");
-
-                    if !has_keyword {
-                        settings.extensions.push(ExtensionSetting {
-                            extension_id: extension.id.to_owned(),
-                            setting_id: String::from("keyword"),
-                            setting_value: extension.keyword.to_owned(),
-                        })
+                        .filter(|diagnostic| diagnostic.path == manifest_path)
+                        .cloned(),
+                );
+
+                Some(entry.manifest.to_owned())
+            }
+            None => {
+                let (manifest, manifest_diagnostics) = check_manifest(&manifest_path);
+                diagnostics.extend(manifest_diagnostics);
+                manifest
+            }
+        };
+
+        let extension = match manifest {
+            Some(extension) => extension,
+            None => continue,
+        };
+
+        if let Some(diagnostic) = check_duplicate_id(&manifest_path, &extension, &extensions) {
+            diagnostics.push(diagnostic);
+            continue;
+        }
+
+        extensions.push(extension.to_owned());
+
+        entries.push(ExtensionIndexEntry {
+            manifest: extension.to_owned(),
+            dir: manifest_path.parent().unwrap().to_owned(),
+            manifest_path: manifest_path.to_owned(),
+            modified,
+        });
+    }
+
+    update_settings(|settings| {
+        for extension in &extensions {
+            add_default_settings(settings, extension);
+        }
+    })
+    .expect("Error locking settings");
+
+    let bytes = bincode::serialize(&entries).expect("Error serializing extensions");
+    write_file_atomic(get_indexing_extensions_path(), &bytes).expect("Error writing extensions");
+
+    let bytes = bincode::serialize(&diagnostics).expect("Error serializing diagnostics");
+    write_file_atomic(get_indexing_extension_diagnostics_path(), &bytes)
+        .expect("Error writing diagnostics");
+
+    IndexReport::new(&previous_entries, &entries)
+}
+
+/// The problems found in the manifests the last time the extensions were indexed
+pub fn get_extension_diagnostics() -> Vec<ManifestDiagnostic> {
+    match fs::read(get_indexing_extension_diagnostics_path()) {
+        Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Reports every problem found in a manifest file.
+///
+/// Duplicate extension ids can only be found with [`validate_extensions`].
+pub fn validate_manifest(path: impl AsRef<Path>) -> Vec<ManifestDiagnostic> {
+    check_manifest(path.as_ref()).1
+}
+
+/// Reports every problem found in the manifests of the extensions directory, including duplicate extension ids
+pub fn validate_extensions() -> Vec<ManifestDiagnostic> {
+    let mut extensions = Vec::<ExtensionManifest>::new();
+    let mut diagnostics = Vec::<ManifestDiagnostic>::new();
+
+    for manifest_path in get_manifest_paths() {
+        let (manifest, manifest_diagnostics) = check_manifest(&manifest_path);
+        diagnostics.extend(manifest_diagnostics);
+
+        if let Some(extension) = manifest {
+            match check_duplicate_id(&manifest_path, &extension, &extensions) {
+                Some(diagnostic) => diagnostics.push(diagnostic),
+                None => extensions.push(extension),
+            }
+        }
+    }
+
+    diagnostics
+}
+
+pub fn get_extensions() -> Vec<ExtensionManifest> {
+    match try_get_extensions() {
+        Ok(extensions) => extensions,
+        Err(Error::Bincode(_)) => Vec::new(),
+        Err(error) => panic!("Error reading extensions: {error}"),
+    }
+}
+
+pub fn try_get_extensions() -> Result<Vec<ExtensionManifest>> {
+    let entries = try_get_extension_index()?;
+    Ok(entries.into_iter().map(|entry| entry.manifest).collect())
+}
+
+/// Gets the indexed extensions along with the directory they are installed in
+pub fn get_extension_index() -> Vec<ExtensionIndexEntry> {
+    match try_get_extension_index() {
+        Ok(entries) => entries,
+        Err(Error::Bincode(_)) => Vec::new(),
+        Err(error) => panic!("Error reading extensions: {error}"),
+    }
+}
+
+pub fn try_get_extension_index() -> Result<Vec<ExtensionIndexEntry>> {
+    let bytes = read_file(get_indexing_extensions_path())?;
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+/// Installs an extension from a directory, a `.tar.gz` or a `.zip` archive and indexes the extensions again.
+///
+/// Fails if an extension with the same id is already installed. Use [`upgrade_extension`] to replace it.
+pub fn install_extension(source: impl AsRef<Path>) -> Result<ExtensionManifest> {
+    install_extension_from(source.as_ref(), false)
+}
+
+/// Same as [`install_extension`] but replaces the installed extension with the same id
+pub fn upgrade_extension(source: impl AsRef<Path>) -> Result<ExtensionManifest> {
+    install_extension_from(source.as_ref(), true)
+}
+
+/// Removes the extension and its settings, and indexes the extensions again
+pub fn uninstall_extension(extension_id: impl Into<String>) -> Result<()> {
+    let extension_id = extension_id.into();
+    let extension_dir = get_extension_dir(&extension_id)
+        .ok_or_else(|| Error::ExtensionNotFound(extension_id.to_owned()))?;
+
+    #[cfg(unix)]
+    if is_daemon_running(&extension_id) {
+        shutdown_daemon(&extension_id);
+    }
+
+    fs::remove_dir_all(extension_dir)?;
+    delete_extension_secrets(&extension_id)?;
+
+    update_settings(|settings| {
+        settings
+            .extensions
+            .retain(|setting| setting.extension_id != extension_id)
+    })?;
+
+    index_extensions();
+
+    Ok(())
+}
+
+/// Checks if the extension speaks a protocol version the launcher supports.
+///
+/// Returns the version both sides will use.
+pub fn check_extension_protocol(extension: &ExtensionManifest) -> Result<u32> {
+    if extension.min_protocol > PROTOCOL_VERSION {
+        return Err(Error::UnsupportedProtocol(extension.min_protocol));
+    }
+
+    let version = extension.protocol.min(PROTOCOL_VERSION);
+    check_protocol_version(version)?;
+
+    Ok(version)
+}
+
+/// Picks the transport the launcher and the extension will use to exchange results
+pub fn get_extension_transport(extension: &ExtensionManifest) -> TransportKind {
+    negotiate_transport(&extension.get_transports())
+}
+
+/// Reads the search results an extension sends through the transport for the given request as they arrive.
+///
+/// Messages that aren't search results, like debug prints on stdout, and results answering other requests are skipped.
+/// An [`Error::Extension`] is returned when the extension reports that it failed.
+pub struct SearchResultsStream<'a> {
+    transport: &'a mut dyn Transport,
+    request_id: String,
+    done: bool,
+}
+
+impl<'a> SearchResultsStream<'a> {
+    pub fn new(transport: &'a mut dyn Transport, request_id: impl Into<String>) -> Self {
+        Self {
+            transport,
+            request_id: request_id.into(),
+            done: false,
+        }
+    }
+
+    fn is_current(&self, request_id: &Option<String>) -> bool {
+        match request_id {
+            Some(id) => *id == self.request_id,
+            None => true,
+        }
+    }
+}
+
+impl Iterator for SearchResultsStream<'_> {
+    type Item = Result<SearchResults>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let message = match self.transport.receive() {
+                Ok(Some(message)) => message,
+                Ok(None) => {
+                    self.done = true;
+                    break;
+                }
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error.into()));
+                }
+            };
+
+            let (version, message) = match decode_versioned_json(&message) {
+                Ok(decoded) => decoded,
+                Err(Error::UnsupportedProtocol(version)) => {
+                    self.done = true;
+                    return Some(Err(Error::UnsupportedProtocol(version)));
+                }
+                // Protocol 1 extensions on windows send a single binary message
+                Err(_) => match bincode::deserialize::<SearchResultsV1>(&message) {
+                    Ok(results) => {
+                        self.done = true;
+                        return Some(Ok(results.into()));
                     }
+                    Err(_) => continue,
+                },
+            };
 
-                    if let Some(extension_settings) = extension.settings {
-                        for extension_setting in extension_settings {
-                            let has_setting = settings.extensions.iter().any(|es| {
-                                es.extension_id == extension.id
-                                    && es.setting_id == extension_setting.id
-                            });
-
-                            if !has_setting {
-                                settings.extensions.push(ExtensionSetting {
-                                    extension_id: extension.id.to_owned(),
-                                    setting_id: extension_setting.id.to_owned(),
-                                    setting_value: extension_setting.default_value.to_owned(),
-                                })
-                            }
-                        }
+            match message {
+                SearchResultsMessage::Batch(results) => {
+                    if !self.is_current(&results.request_id) {
+                        continue;
+                    }
+
+                    // Protocol 1 extensions send all their results in a single message
+                    if version == 1 {
+                        self.done = true;
+                    }
+
+                    return Some(Ok(results));
+                }
+                SearchResultsMessage::Done { request_id } => {
+                    if self.is_current(&request_id) {
+                        self.done = true;
+                    }
+                }
+                SearchResultsMessage::Error(error) => {
+                    if self.is_current(&error.request_id) {
+                        self.done = true;
+                        return Some(Err(error.into()));
                     }
                 }
             }
         }
+
+        None
     }
+}
 
-    write_settings(settings);
+/// Waits for every batch of results the extension sends for the request and merges them.
+///
+/// Fails with [`Error::NoResponse`] when the extension exits without sending anything.
+pub fn read_search_results(
+    transport: &mut dyn Transport,
+    request_id: impl Into<String>,
+) -> Result<SearchResults> {
+    let mut stream = SearchResultsStream::new(transport, request_id);
+    let mut search_results = stream.next().ok_or(Error::NoResponse)??;
 
-    let bytes = bincode::serialize(&extensions).expect("Error serializing extensions");
-    fs::write(&get_indexing_extensions_path(), &bytes).expect("Error writing extensions");
+    for batch in stream {
+        search_results.results.extend(batch?.results);
+    }
+
+    Ok(search_results)
 }
 
-pub fn get_extensions() -> Vec<ExtensionManifest> {
-    let path = get_indexing_extensions_path();
-    let bytes = fs::read(path).expect("Error reading extensions");
+/// Writes the request to its own file so concurrent requests don't overwrite each other.
+///
+/// It's also written to the shared request file, using protocol 1, for extensions that don't know their request id.
+pub fn write_extension_request(request: ExtensionRequest) {
+    write_extension_request_as(request, MessageEncoding::Binary);
+}
 
-    match bincode::deserialize(&bytes) {
-        Ok(extensions) => extensions,
-        Err(_) => Vec::new(),
+/// Same as [`write_extension_request`] but with the encoding the extension reads, see [`ExtensionManifest::encoding`]
+pub fn write_extension_request_as(request: ExtensionRequest, encoding: MessageEncoding) {
+    let bytes = encode_message(&request, encoding).expect("Error serializing request");
+    let requests_dir = get_extension_requests_dir();
+
+    if !requests_dir.exists() {
+        fs::create_dir_all(&requests_dir).expect("Error creating extension requests directory");
     }
+
+    write_api_file(
+        &get_request_file(&requests_dir, &request.request_id),
+        &bytes,
+    );
+
+    let legacy_bytes =
+        bincode::serialize(&ExtensionRequestV1::from(request)).expect("Error serializing request");
+
+    write_api_file(&get_extension_request_path(), &legacy_bytes);
 }
 
-pub fn write_extension_request(request: ExtensionRequest) {
-    let bytes = bincode::serialize(&request).expect("Error serializing request");
+/// Gets the request the extension was launched for
+pub fn get_extension_request() -> ExtensionRequest {
+    try_get_extension_request().expect("Error getting extension request")
+}
+
+pub fn try_get_extension_request() -> Result<ExtensionRequest> {
+    if let Some(request) = get_daemon_request() {
+        return Ok(request);
+    }
+
+    let path = match get_current_request_id() {
+        Some(request_id) => get_request_file(&get_extension_requests_dir(), &request_id),
+        None => get_extension_request_path(),
+    };
+
+    let bytes = read_file(path)?;
+    decode_message(&bytes)
+}
+
+pub fn write_form_request(request: OpenFormAction) {
+    write_form_request_as(request, MessageEncoding::Binary);
+}
+
+pub fn write_form_request_as(request: OpenFormAction, encoding: MessageEncoding) {
+    let bytes = encode_message(&request, encoding).expect("Error serializing request");
+    fs::write(get_form_request_path(), &bytes).expect("Error writing request");
+}
+
+pub fn get_form_request() -> OpenFormAction {
+    try_get_form_request().expect("Error getting form request")
+}
+
+pub fn try_get_form_request() -> Result<OpenFormAction> {
+    let bytes = read_file(get_form_request_path())?;
+    decode_message(&bytes)
+}
+
+/// Writes the form response for the request set in [`FormResponse::request_id`].
+///
+/// It's also written to the shared response file, using protocol 1, for extensions that don't know their request id.
+pub fn write_form_response(response: FormResponse) {
+    write_form_response_as(response, MessageEncoding::Binary);
+}
+
+/// Same as [`write_form_response`] but with the encoding the extension reads, see [`ExtensionManifest::encoding`]
+pub fn write_form_response_as(response: FormResponse, encoding: MessageEncoding) {
+    if let Some(request_id) = &response.request_id {
+        let bytes = encode_message(&response, encoding).expect("Error serializing response");
+        let responses_dir = get_form_responses_dir();
+
+        if !responses_dir.exists() {
+            fs::create_dir_all(&responses_dir).expect("Error creating form responses directory");
+        }
+
+        write_api_file(&get_request_file(&responses_dir, request_id), &bytes);
+    }
+
+    let legacy_bytes =
+        bincode::serialize(&FormResponseV1::from(response)).expect("Error serializing response");
+
+    write_api_file(&get_extension_response_path(), &legacy_bytes);
+}
+
+/// Gets the form response sent with the request the extension was launched for
+pub fn get_form_response() -> FormResponse {
+    try_get_form_response().expect("Error getting form response")
+}
+
+/// Only extensions that don't know their request id read the shared response file, since it may belong to another request.
+pub fn try_get_form_response() -> Result<FormResponse> {
+    if let Some(request_id) = get_current_request_id() {
+        let path = get_request_file(&get_form_responses_dir(), &request_id);
+        let response = decode_message::<FormResponse>(&read_file(path.to_owned())?)?;
+
+        return match &response.request_id {
+            Some(id) if *id == request_id => Ok(response),
+            _ => Err(Error::MissingFile(path)),
+        };
+    }
+
+    let bytes = read_file(get_extension_response_path())?;
+    decode_message(&bytes)
+}
+
+/// Gets the form response of a request. Responses that belong to another request are discarded.
+pub fn get_form_response_for(request_id: impl Into<String>) -> Option<FormResponse> {
+    let request_id = request_id.into();
+    let bytes = fs::read(get_request_file(&get_form_responses_dir(), &request_id)).ok()?;
+    let response = decode_message::<FormResponse>(&bytes).ok()?;
+
+    match &response.request_id {
+        Some(id) if *id == request_id => Some(response),
+        _ => None,
+    }
+}
+
+/// Tells the extension answering the request that its results are no longer needed
+pub fn cancel_extension_request(request_id: impl Into<String>) {
+    let cancelled_dir = get_cancelled_requests_dir();
+
+    if !cancelled_dir.exists() {
+        fs::create_dir_all(&cancelled_dir).expect("Error creating cancelled requests directory");
+    }
+
+    let mut path = cancelled_dir;
+    path.push(request_id.into());
+    fs::write(path, []).expect("Error cancelling request");
+}
+
+/// Builds the command that runs the extension, as declared in its manifest.
+///
+/// Daemon extensions are told to run as a daemon. The request environment, like the transport, still has to be set by the caller.
+pub fn get_extension_command(extension: &ExtensionManifest) -> Result<Command> {
+    let extension_dir = get_extension_dir(&extension.id)
+        .ok_or_else(|| Error::ExtensionNotFound(extension.id.to_owned()))?;
+
+    let mut exec = extension_dir.to_owned();
+    exec.push(extension.get_exec());
+
+    let mut command = match &extension.runtime {
+        Some(runtime) => {
+            let mut command = Command::new(runtime);
+            command.args(&extension.runtime_args).arg(exec);
+            command
+        }
+        None => Command::new(exec),
+    };
+
+    let mut cwd = extension_dir;
+
+    if let Some(dir) = &extension.cwd {
+        cwd.push(dir);
+    }
+
+    command
+        .args(&extension.args)
+        .envs(&extension.env)
+        .current_dir(cwd);
+
+    if extension.mode == ExtensionMode::Daemon {
+        extension.set_daemon_env(&mut command);
+    }
+
+    Ok(command)
+}
+
+/// The time the launcher waits for the extension before killing it
+pub fn get_extension_timeout(extension: &ExtensionManifest) -> Duration {
+    match extension.timeout {
+        Some(timeout) => Duration::from_millis(timeout),
+        None => DEFAULT_EXTENSION_TIMEOUT,
+    }
+}
+
+/// Waits for the extension process to exit. It's killed if it's still running after the timeout, in which case `None` is returned.
+pub fn wait_for_extension(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Some(status);
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
 
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Sends the request to an extension running as a daemon. The results are read from the returned transport.
+///
+/// The daemon may still be starting, so connecting is retried until the timeout.
+#[cfg(unix)]
+pub fn send_daemon_request(
+    request: &ExtensionRequest,
+    timeout: Duration,
+) -> io::Result<SocketTransport> {
+    let socket_path = get_extension_daemon_socket_path(&request.extension_id);
+    let deadline = Instant::now() + timeout;
+
+    let stream = loop {
+        match UnixStream::connect(&socket_path) {
+            Ok(stream) => break stream,
+            Err(error) => {
+                if Instant::now() >= deadline {
+                    return Err(error);
+                }
+
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    };
+
+    let mut transport = SocketTransport::from_stream(stream).set_timeout(timeout);
+    let message = encode_json(request).map_err(io::Error::other)?;
+    transport.send(&message)?;
+
+    Ok(transport)
+}
+
+#[cfg(unix)]
+pub fn is_daemon_running(extension_id: impl Into<String>) -> bool {
+    UnixStream::connect(get_extension_daemon_socket_path(extension_id)).is_ok()
+}
+
+/// Asks an extension running as a daemon to stop
+#[cfg(unix)]
+pub fn shutdown_daemon(extension_id: impl Into<String>) {
+    let request = ExtensionRequest::new_shutdown_request(extension_id);
+    let _ = send_daemon_request(&request, Duration::ZERO);
+}
+
+/// Removes the files of a request once the extension answered it
+pub fn finish_extension_request(request_id: impl Into<String>) {
+    let request_id = request_id.into();
+
+    let mut cancelled_path = get_cancelled_requests_dir();
+    cancelled_path.push(&request_id);
+
+    if cancelled_path.exists() {
+        fs::remove_file(cancelled_path).expect("Error removing cancelled request");
+    }
+
+    let paths = [
+        get_request_file(&get_extension_requests_dir(), &request_id),
+        get_request_file(&get_form_responses_dir(), &request_id),
+        get_extension_response_path_for(&request_id),
+    ];
+
+    for path in paths {
+        if path.exists() {
+            fs::remove_file(path).expect("Error removing request file");
+        }
+    }
+}
+
+fn read_file(path: PathBuf) -> Result<Vec<u8>> {
+    fs::read(&path).map_err(|error| Error::from_read(error, path))
+}
+
+fn get_request_file(dir: &Path, request_id: &str) -> PathBuf {
+    let mut path = dir.to_owned();
+    path.push(format!("{request_id}.bin"));
+    path
+}
+
+fn write_api_file(path: &Path, bytes: &[u8]) {
     #[cfg(target_os = "linux")]
     {
-        fs::write(get_extension_request_path(), &bytes).expect("Error writing extension request");
+        fs::write(path, bytes).expect("Error writing api file");
     }
 
     #[cfg(target_os = "windows")]
     {
-        let mut file = File::create(get_extension_request_path()).unwrap();
-        file.write_all(&bytes).unwrap();
+        let mut file = File::create(path).unwrap();
+        file.write_all(bytes).unwrap();
         file.flush().unwrap();
         file.sync_all().unwrap();
     }
 }
 
-pub fn get_extension_request() -> ExtensionRequest {
-    let bytes = fs::read(get_extension_request_path()).expect("Error reading extension request");
-    let request = bincode::deserialize(&bytes).expect("Error deserializing extension request");
-    request
+/// Adds the keyword and the settings of the extension that aren't in the settings yet, with their default values
+fn add_default_settings(settings: &mut Settings, extension: &ExtensionManifest) {
+    let has_keyword = settings
+        .extensions
+        .iter()
+        .any(|es| es.extension_id == extension.id && es.setting_id == "keyword");
+
+    if !has_keyword {
+        settings.extensions.push(ExtensionSetting {
+            extension_id: extension.id.to_owned(),
+            setting_id: String::from("keyword"),
+            setting_value: extension.keyword.to_owned(),
+        })
+    }
+
+    for extension_setting in extension.settings.iter().flatten() {
+        if extension_setting.setting_type.is_secret() {
+            move_setting_to_secrets(settings, &extension.id, &extension_setting.id);
+            continue;
+        }
+
+        let has_setting = settings
+            .extensions
+            .iter()
+            .any(|es| es.extension_id == extension.id && es.setting_id == extension_setting.id);
+
+        if !has_setting {
+            settings.extensions.push(ExtensionSetting {
+                extension_id: extension.id.to_owned(),
+                setting_id: extension_setting.id.to_owned(),
+                setting_value: extension_setting.default_value.to_owned(),
+            })
+        }
+    }
 }
 
-pub fn write_form_request(request: OpenFormAction) {
-    let bytes = bincode::serialize(&request).expect("Error serializing request");
-    fs::write(&get_form_request_path(), &bytes).expect("Error writing request");
+/// Moves a password setting stored in the settings by an older version to the secrets
+fn move_setting_to_secrets(settings: &mut Settings, extension_id: &str, setting_id: &str) {
+    let position = settings
+        .extensions
+        .iter()
+        .position(|s| s.extension_id == extension_id && s.setting_id == setting_id);
+
+    if let Some(position) = position {
+        let setting = &settings.extensions[position];
+
+        if setting.setting_value.is_empty()
+            || set_secret(extension_id, setting_id, &setting.setting_value).is_ok()
+        {
+            settings.extensions.remove(position);
+        }
+    }
 }
 
-pub fn get_form_request() -> OpenFormAction {
-    let bytes = fs::read(get_form_request_path()).expect("Error reading form request");
-    let request = bincode::deserialize(&bytes).expect("Error deserializing form request");
-    request
+/// The manifests at the root of each extension directory, sorted so duplicates are always resolved the same way
+pub(crate) fn get_manifest_paths() -> Vec<PathBuf> {
+    let mut paths = match fs::read_dir(get_extensions_dir()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| {
+                let mut path = entry.path();
+                path.push("manifest.json");
+                path
+            })
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+
+    paths.sort();
+    paths
 }
 
-pub fn write_form_response(response: FormResponse) {
-    let bytes = bincode::serialize(&response).expect("Error serializing response");
-    fs::write(get_extension_response_path(), &bytes).expect("Error writing response");
+fn get_modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
 }
 
-pub fn get_form_response() -> FormResponse {
-    let bytes = fs::read(get_extension_response_path()).expect("Error reading form response");
-    let response = bincode::deserialize(&bytes).expect("Error deserializing form response");
-    response
+/// Parses the manifest and checks it. The manifest is only returned when it can be used.
+fn check_manifest(path: &Path) -> (Option<ExtensionManifest>, Vec<ManifestDiagnostic>) {
+    let mut diagnostics = Vec::new();
+
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(error) => {
+            diagnostics.push(ManifestDiagnostic::error(path, "", error.to_string()));
+            return (None, diagnostics);
+        }
+    };
+
+    let value = match serde_json::from_str::<Value>(&json) {
+        Ok(value) => value,
+        Err(error) => {
+            diagnostics.push(ManifestDiagnostic::from_json_error(path, &error));
+            return (None, diagnostics);
+        }
+    };
+
+    check_required_fields(path, &value, &mut diagnostics);
+
+    let manifest = match serde_json::from_str::<ExtensionManifest>(&json) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            // Missing fields were already reported with their location
+            if diagnostics.is_empty() {
+                diagnostics.push(ManifestDiagnostic::from_json_error(path, &error));
+            }
+
+            return (None, diagnostics);
+        }
+    };
+
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+    {
+        return (None, diagnostics);
+    }
+
+    check_os(path, "/os", &manifest.os, &mut diagnostics);
+    check_version(path, "/version", &manifest.version, &mut diagnostics);
+    check_version(
+        path,
+        "/min_core_version",
+        &manifest.min_core_version,
+        &mut diagnostics,
+    );
+
+    if !manifest.is_supported_by_core() {
+        diagnostics.push(ManifestDiagnostic::error(
+            path,
+            "/min_core_version",
+            format!(
+                "The extension needs version {} or newer. The current version is {CORE_VERSION}",
+                manifest.min_core_version.as_deref().unwrap_or_default()
+            ),
+        ));
+
+        return (None, diagnostics);
+    }
+
+    let settings = manifest.settings.to_owned().unwrap_or_default();
+
+    for (index, setting) in settings.iter().enumerate() {
+        let pointer = format!("/settings/{index}");
+
+        if settings[..index].iter().any(|s| s.id == setting.id) {
+            diagnostics.push(ManifestDiagnostic::warning(
+                path,
+                format!("{pointer}/id"),
+                format!("Duplicate setting id `{}`", setting.id),
+            ));
+        }
+
+        check_os(
+            path,
+            &format!("{pointer}/os"),
+            &setting.os,
+            &mut diagnostics,
+        );
+
+        if setting.setting_type != ExtensionManifestSettingType::Select {
+            if let Err(Error::InvalidSetting { message, .. }) =
+                setting.validate_value(&setting.default_value)
+            {
+                diagnostics.push(ManifestDiagnostic::warning(
+                    path,
+                    format!("{pointer}/default_value"),
+                    message,
+                ));
+            }
+        }
+
+        if let ExtensionManifestSettingType::Select = setting.setting_type {
+            match &setting.select_options {
+                Some(options) => {
+                    if !options.iter().any(|o| o.id == setting.default_value) {
+                        diagnostics.push(ManifestDiagnostic::warning(
+                            path,
+                            format!("{pointer}/default_value"),
+                            format!(
+                                "Default value `{}` is not one of the select options",
+                                setting.default_value
+                            ),
+                        ));
+                    }
+                }
+                None => diagnostics.push(ManifestDiagnostic::warning(
+                    path,
+                    format!("{pointer}/select_options"),
+                    "Select settings must have select options",
+                )),
+            }
+        }
+
+        for (condition_index, condition) in setting.show_conditions.iter().flatten().enumerate() {
+            check_show_condition(
+                path,
+                &format!("{pointer}/show_conditions/{condition_index}"),
+                condition,
+                &settings,
+                &mut diagnostics,
+            );
+        }
+    }
+
+    (Some(manifest), diagnostics)
+}
+
+/// Checks a show condition and its groups for unknown settings and incomplete comparisons
+fn check_show_condition(
+    path: &Path,
+    pointer: &str,
+    condition: &ExtensionManifestShowCondition,
+    settings: &[ExtensionManifestSetting],
+    diagnostics: &mut Vec<ManifestDiagnostic>,
+) {
+    if !condition.setting_id.is_empty() && !settings.iter().any(|s| s.id == condition.setting_id) {
+        diagnostics.push(ManifestDiagnostic::warning(
+            path,
+            format!("{pointer}/setting_id"),
+            format!(
+                "Show condition references unknown setting `{}`",
+                condition.setting_id
+            ),
+        ));
+    }
+
+    if condition.operator == ShowConditionOperator::OneOf && condition.setting_values.is_empty() {
+        diagnostics.push(ManifestDiagnostic::warning(
+            path,
+            format!("{pointer}/setting_values"),
+            "`OneOf` show condition has no values",
+        ));
+    }
+
+    if let Some(os) = &condition.os {
+        if !matches!(os.as_str(), "*" | "linux" | "windows") {
+            diagnostics.push(ManifestDiagnostic::warning(
+                path,
+                format!("{pointer}/os"),
+                format!("Unknown os `{os}`"),
+            ));
+        }
+    }
+
+    let groups = [("all", &condition.all), ("any", &condition.any)];
+
+    for (group_name, group) in groups {
+        for (index, nested) in group.iter().flatten().enumerate() {
+            check_show_condition(
+                path,
+                &format!("{pointer}/{group_name}/{index}"),
+                nested,
+                settings,
+                diagnostics,
+            );
+        }
+    }
+}
+
+/// Reports the required fields that are missing or have the wrong type, which serde stops at the first of
+fn check_required_fields(path: &Path, value: &Value, diagnostics: &mut Vec<ManifestDiagnostic>) {
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => {
+            diagnostics.push(ManifestDiagnostic::error(
+                path,
+                "",
+                "The manifest must be a json object",
+            ));
+            return;
+        }
+    };
+
+    check_string_fields(
+        path,
+        "",
+        object,
+        &["id", "name", "description", "keyword"],
+        diagnostics,
+    );
+
+    if let Some(Value::Array(settings)) = object.get("settings") {
+        for (index, setting) in settings.iter().enumerate() {
+            let pointer = format!("/settings/{index}");
+
+            match setting.as_object() {
+                Some(setting) => check_string_fields(
+                    path,
+                    &pointer,
+                    setting,
+                    &[
+                        "id",
+                        "title",
+                        "description",
+                        "setting_type",
+                        "default_value",
+                    ],
+                    diagnostics,
+                ),
+                None => diagnostics.push(ManifestDiagnostic::error(
+                    path,
+                    pointer,
+                    "Settings must be json objects",
+                )),
+            }
+        }
+    }
+}
+
+fn check_string_fields(
+    path: &Path,
+    pointer: &str,
+    object: &Map<String, Value>,
+    fields: &[&str],
+    diagnostics: &mut Vec<ManifestDiagnostic>,
+) {
+    for field in fields {
+        match object.get(*field) {
+            Some(Value::String(_)) => {}
+            Some(_) => diagnostics.push(ManifestDiagnostic::error(
+                path,
+                format!("{pointer}/{field}"),
+                format!("`{field}` must be a string"),
+            )),
+            None => diagnostics.push(ManifestDiagnostic::error(
+                path,
+                pointer,
+                format!("Missing field `{field}`"),
+            )),
+        }
+    }
+}
+
+fn check_os(path: &Path, pointer: &str, os: &str, diagnostics: &mut Vec<ManifestDiagnostic>) {
+    if !["*", "linux", "windows"].contains(&os) {
+        diagnostics.push(ManifestDiagnostic::warning(
+            path,
+            pointer,
+            format!("Invalid os `{os}`. Valid values are `*`, `linux` and `windows`"),
+        ));
+    }
+}
+
+fn check_version(
+    path: &Path,
+    pointer: &str,
+    version: &Option<String>,
+    diagnostics: &mut Vec<ManifestDiagnostic>,
+) {
+    if let Some(version) = version {
+        if parse_version(version).is_none() {
+            diagnostics.push(ManifestDiagnostic::warning(
+                path,
+                pointer,
+                format!("`{version}` is not a semantic version, like `1.2.0`"),
+            ));
+        }
+    }
+}
+
+fn check_duplicate_id(
+    path: &Path,
+    extension: &ExtensionManifest,
+    extensions: &[ExtensionManifest],
+) -> Option<ManifestDiagnostic> {
+    if extensions.iter().any(|e| e.id == extension.id) {
+        Some(ManifestDiagnostic::error(
+            path,
+            "/id",
+            format!(
+                "Another extension already uses the id `{}`. This one is ignored",
+                extension.id
+            ),
+        ))
+    } else {
+        None
+    }
+}
+
+fn install_extension_from(source: &Path, upgrade: bool) -> Result<ExtensionManifest> {
+    let staging_dir = get_extensions_staging_dir();
+    fs::create_dir_all(&staging_dir)?;
+
+    let mut stage = staging_dir.to_owned();
+    stage.push(new_request_id());
+
+    let result = stage_extension(source, &stage, upgrade);
+
+    if stage.exists() {
+        let _ = fs::remove_dir_all(&stage);
+    }
+
+    if result.is_ok() {
+        index_extensions();
+    }
+
+    result
+}
+
+/// Unpacks the source in the stage directory, checks it and moves it to the extensions directory
+fn stage_extension(source: &Path, stage: &Path, upgrade: bool) -> Result<ExtensionManifest> {
+    let mut unpacked_dir = stage.to_owned();
+    unpacked_dir.push("unpacked");
+
+    unpack_extension(source, &unpacked_dir)?;
+
+    let extension_dir = find_extension_root(&unpacked_dir)?;
+
+    let mut manifest_path = extension_dir.to_owned();
+    manifest_path.push("manifest.json");
+
+    let (manifest, diagnostics) = check_manifest(&manifest_path);
+
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => return Err(Error::InvalidManifest(diagnostics)),
+    };
+
+    let installed_dir = get_extension_dir(&manifest.id);
+
+    if installed_dir.is_some() && !upgrade {
+        return Err(Error::ExtensionExists(manifest.id));
+    }
+
+    let extensions_dir = get_extensions_dir();
+    fs::create_dir_all(&extensions_dir)?;
+
+    let target_dir = match &installed_dir {
+        Some(dir) => dir.to_owned(),
+        None => {
+            let mut dir = extensions_dir;
+            dir.push(get_extension_dir_name(&manifest.id));
+            dir
+        }
+    };
+
+    if target_dir.exists() {
+        if installed_dir.is_none() {
+            return Err(Error::ExtensionExists(manifest.id));
+        }
+
+        let mut previous_dir = stage.to_owned();
+        previous_dir.push("previous");
+
+        fs::rename(&target_dir, &previous_dir)?;
+
+        if let Err(error) = fs::rename(&extension_dir, &target_dir) {
+            fs::rename(&previous_dir, &target_dir)?;
+            return Err(error.into());
+        }
+    } else {
+        fs::rename(&extension_dir, &target_dir)?;
+    }
+
+    #[cfg(unix)]
+    if upgrade && is_daemon_running(&manifest.id) {
+        shutdown_daemon(&manifest.id);
+    }
+
+    Ok(manifest)
+}
+
+fn unpack_extension(source: &Path, target: &Path) -> Result<()> {
+    let name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if source.is_dir() {
+        copy_dir(source, target)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        let file = File::open(source).map_err(|error| Error::from_read(error, source))?;
+        tar::Archive::new(GzDecoder::new(file)).unpack(target)?;
+        Ok(())
+    } else if name.ends_with(".zip") {
+        let file = File::open(source).map_err(|error| Error::from_read(error, source))?;
+        zip::ZipArchive::new(file)?.extract(target)?;
+        Ok(())
+    } else if !source.exists() {
+        Err(Error::MissingFile(source.to_owned()))
+    } else {
+        Err(Error::UnsupportedArchive(source.to_owned()))
+    }
+}
+
+fn copy_dir(source: &Path, target: &Path) -> Result<()> {
+    for entry in WalkDir::new(source) {
+        let entry = entry.map_err(io::Error::from)?;
+        let relative_path = entry.path().strip_prefix(source).unwrap();
+
+        let mut path = target.to_owned();
+        path.push(relative_path);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&path)?;
+        } else {
+            fs::copy(entry.path(), &path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Archives usually have the extension inside a single directory instead of at the root
+fn find_extension_root(dir: &Path) -> Result<PathBuf> {
+    let mut manifest_path = dir.to_owned();
+    manifest_path.push("manifest.json");
+
+    if manifest_path.exists() {
+        return Ok(dir.to_owned());
+    }
+
+    let entries = fs::read_dir(dir)?.flatten().collect::<Vec<_>>();
+
+    if let [entry] = entries.as_slice() {
+        if entry.path().is_dir() {
+            return find_extension_root(&entry.path());
+        }
+    }
+
+    Err(Error::MissingFile(manifest_path))
+}
+
+/// The name of the directory the extension is installed in. Characters that can't be in a directory name are replaced.
+fn get_extension_dir_name(extension_id: &str) -> String {
+    extension_id
+        .chars()
+        .map(|char| match char {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => char,
+            _ => '-',
+        })
+        .collect()
 }
@@ -1,3 +1,7 @@
 pub mod settings;
 pub mod apps;
-pub mod extensions;
\ No newline at end of file
+pub mod extensions;
+pub mod secrets;
+pub mod store;
+#[cfg(feature = "watcher")]
+pub mod watcher;
\ No newline at end of file
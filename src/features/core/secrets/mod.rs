@@ -0,0 +1,267 @@
+use std::{collections::HashMap, fs};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, OsRng},
+    AeadCore, ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    paths::{get_secrets_key_path, get_secrets_path},
+    utils::{lock_file, write_private_file_atomic},
+};
+
+/// Where a secret is stored
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretBackend {
+    /// The Secret Service of the desktop, like GNOME Keyring or KWallet. Needs the `secret-service` feature.
+    SecretService,
+
+    /// A local file encrypted with a key stored next to it. Used when the Secret Service isn't available, like in
+    /// headless machines.
+    File,
+}
+
+/// The secrets file. Only the encrypted map of secrets is written to the disk.
+#[derive(Serialize, Deserialize)]
+struct EncryptedSecrets {
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Gets the value of a secret setting. Secrets are never stored in the settings.
+pub fn get_secret(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Result<Option<String>> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+
+    #[cfg(all(target_os = "linux", feature = "secret-service"))]
+    if let Ok(Some(secret)) = secret_service_backend::get(&extension_id, &setting_id) {
+        return Ok(Some(secret));
+    }
+
+    let secrets = read_file_secrets()?;
+    Ok(secrets
+        .get(&get_secret_key(&extension_id, &setting_id))
+        .cloned())
+}
+
+/// Stores the value of a secret setting. The Secret Service is preferred and the encrypted file is the fallback.
+///
+/// Returns where the secret was stored.
+pub fn set_secret(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+    value: impl Into<String>,
+) -> Result<SecretBackend> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+    let value = value.into();
+    let key = get_secret_key(&extension_id, &setting_id);
+
+    let _lock = lock_file(get_secrets_path())?;
+    let mut secrets = read_file_secrets()?;
+
+    #[cfg(all(target_os = "linux", feature = "secret-service"))]
+    if secret_service_backend::set(&extension_id, &setting_id, &value).is_ok() {
+        if secrets.remove(&key).is_some() {
+            write_file_secrets(&secrets)?;
+        }
+
+        return Ok(SecretBackend::SecretService);
+    }
+
+    secrets.insert(key, value);
+    write_file_secrets(&secrets)?;
+
+    Ok(SecretBackend::File)
+}
+
+pub fn delete_secret(extension_id: impl Into<String>, setting_id: impl Into<String>) -> Result<()> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+
+    #[cfg(all(target_os = "linux", feature = "secret-service"))]
+    let _ = secret_service_backend::delete(&extension_id, Some(&setting_id));
+
+    let _lock = lock_file(get_secrets_path())?;
+    let mut secrets = read_file_secrets()?;
+
+    if secrets
+        .remove(&get_secret_key(&extension_id, &setting_id))
+        .is_some()
+    {
+        write_file_secrets(&secrets)?;
+    }
+
+    Ok(())
+}
+
+/// Deletes every secret of the extension, like when it's uninstalled
+pub fn delete_extension_secrets(extension_id: impl Into<String>) -> Result<()> {
+    let extension_id = extension_id.into();
+
+    #[cfg(all(target_os = "linux", feature = "secret-service"))]
+    let _ = secret_service_backend::delete(&extension_id, None);
+
+    let _lock = lock_file(get_secrets_path())?;
+    let mut secrets = read_file_secrets()?;
+    let prefix = get_secret_key(&extension_id, "");
+    let count = secrets.len();
+
+    secrets.retain(|key, _| !key.starts_with(&prefix));
+
+    if secrets.len() != count {
+        write_file_secrets(&secrets)?;
+    }
+
+    Ok(())
+}
+
+fn get_secret_key(extension_id: &str, setting_id: &str) -> String {
+    format!("{extension_id}\n{setting_id}")
+}
+
+fn read_file_secrets() -> Result<HashMap<String, String>> {
+    let path = get_secrets_path();
+
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let bytes = fs::read(&path).map_err(|error| Error::from_read(error, &path))?;
+    let encrypted: EncryptedSecrets = bincode::deserialize(&bytes)?;
+
+    if encrypted.nonce.len() != 12 {
+        return Err(Error::SecretStore(String::from("Invalid secrets file")));
+    }
+
+    let cipher = ChaCha20Poly1305::new(&get_file_key()?);
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(&encrypted.nonce),
+            encrypted.ciphertext.as_ref(),
+        )
+        .map_err(|_| Error::SecretStore(String::from("The secrets can't be decrypted")))?;
+
+    Ok(bincode::deserialize(&plaintext)?)
+}
+
+fn write_file_secrets(secrets: &HashMap<String, String>) -> Result<()> {
+    let cipher = ChaCha20Poly1305::new(&get_file_key()?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let plaintext = bincode::serialize(secrets)?;
+
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_ref())
+        .map_err(|_| Error::SecretStore(String::from("The secrets can't be encrypted")))?;
+
+    let encrypted = EncryptedSecrets {
+        nonce: nonce.to_vec(),
+        ciphertext,
+    };
+
+    write_private_file_atomic(get_secrets_path(), bincode::serialize(&encrypted)?)?;
+
+    Ok(())
+}
+
+/// Gets the key of the secrets file, creating it the first time
+fn get_file_key() -> Result<Key> {
+    let path = get_secrets_key_path();
+
+    if path.exists() {
+        let bytes = fs::read(&path).map_err(|error| Error::from_read(error, &path))?;
+
+        if bytes.len() != 32 {
+            return Err(Error::SecretStore(String::from("Invalid secrets key")));
+        }
+
+        return Ok(*Key::from_slice(&bytes));
+    }
+
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    write_private_file_atomic(&path, key)?;
+
+    Ok(key)
+}
+
+#[cfg(all(target_os = "linux", feature = "secret-service"))]
+mod secret_service_backend {
+    use std::collections::HashMap;
+
+    use secret_service::{blocking::SecretService, EncryptionType};
+
+    const APPLICATION: &str = "whiskers-launcher";
+
+    pub fn get(
+        extension_id: &str,
+        setting_id: &str,
+    ) -> Result<Option<String>, secret_service::Error> {
+        let service = SecretService::connect(EncryptionType::Dh)?;
+        let attributes = get_attributes(extension_id, Some(setting_id));
+        let items = service.search_items(attributes)?;
+
+        let item = match items.unlocked.first().or(items.locked.first()) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+
+        item.ensure_unlocked()?;
+        let secret = item.get_secret()?;
+
+        Ok(String::from_utf8(secret).ok())
+    }
+
+    pub fn set(
+        extension_id: &str,
+        setting_id: &str,
+        value: &str,
+    ) -> Result<(), secret_service::Error> {
+        let service = SecretService::connect(EncryptionType::Dh)?;
+        let collection = service.get_default_collection()?;
+        collection.ensure_unlocked()?;
+
+        collection.create_item(
+            &format!("Whiskers Launcher: {extension_id} {setting_id}"),
+            get_attributes(extension_id, Some(setting_id)),
+            value.as_bytes(),
+            true,
+            "text/plain",
+        )?;
+
+        Ok(())
+    }
+
+    /// Deletes one secret of the extension, or all of them when there's no setting id
+    pub fn delete(
+        extension_id: &str,
+        setting_id: Option<&str>,
+    ) -> Result<(), secret_service::Error> {
+        let service = SecretService::connect(EncryptionType::Dh)?;
+        let items = service.search_items(get_attributes(extension_id, setting_id))?;
+
+        for item in items.unlocked.iter().chain(&items.locked) {
+            item.delete()?;
+        }
+
+        Ok(())
+    }
+
+    fn get_attributes<'a>(
+        extension_id: &'a str,
+        setting_id: Option<&'a str>,
+    ) -> HashMap<&'a str, &'a str> {
+        let mut attributes =
+            HashMap::from([("application", APPLICATION), ("extension_id", extension_id)]);
+
+        if let Some(setting_id) = setting_id {
+            attributes.insert("setting_id", setting_id);
+        }
+
+        attributes
+    }
+}
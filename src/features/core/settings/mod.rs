@@ -1,6 +1,25 @@
-use crate::paths::{get_app_resources_icons_dir, get_autostart_dir, get_settings_path};
-use serde::{Deserialize, Serialize};
-use std::fs;
+use crate::{
+    error::{Error, Result},
+    features::{
+        core::{extensions::try_get_extensions, secrets::set_secret},
+        extensions::{ExtensionManifest, ExtensionManifestSetting},
+    },
+    paths::{
+        get_app_resources_icons_dir, get_autostart_dir, get_imported_settings_files_dir,
+        get_legacy_settings_path, get_settings_generation_path, get_settings_path,
+    },
+    utils::{lock_file, write_file_atomic, FileLock},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    cell::Cell,
+    fs::{self, File},
+    io::{self, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
 
 #[cfg(target_os = "linux")]
 use std::os::unix::fs::PermissionsExt;
@@ -95,6 +114,32 @@ pub struct Settings {
     pub hide_app_icons: bool
 }
 
+/// The layout of the settings before the grid and app icon settings, found in old `settings.bin` files
+#[derive(Serialize, Deserialize)]
+struct SettingsV1 {
+    first_key: String,
+    second_key: Option<String>,
+    third_key: String,
+    auto_start: bool,
+    show_recent_apps: bool,
+    show_search_icon: bool,
+    show_settings_icon: bool,
+    show_placeholder: bool,
+    hide_on_blur: bool,
+    border_radius: usize,
+    border_width: usize,
+    accent_border: bool,
+    show_launch_hint: bool,
+    launch_key: String,
+    blacklist: Vec<String>,
+    search_keyword: String,
+    search_engines: Vec<SearchEngine>,
+    default_search_engine: usize,
+    theme: Theme,
+    extensions: Vec<ExtensionSetting>,
+    wallpaper: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchEngine {
     pub id: usize,
@@ -126,6 +171,68 @@ pub struct ExtensionSetting {
     pub setting_value: String,
 }
 
+impl ExtensionSetting {
+    /// Reads `true`/`false`, `1`/`0`, `yes`/`no` and `on`/`off`
+    pub fn get_bool(&self) -> Option<bool> {
+        parse_bool(&self.setting_value)
+    }
+
+    pub fn get_i64(&self) -> Option<i64> {
+        let value = self.setting_value.trim();
+
+        value.parse::<i64>().ok().or_else(|| {
+            let number = value.parse::<f64>().ok()?;
+
+            // Numbers are stored as floats, so `3.0` is a valid integer
+            if number.fract() == 0.0 && number.abs() <= i64::MAX as f64 {
+                Some(number as i64)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn get_f64(&self) -> Option<f64> {
+        self.setting_value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|number| number.is_finite())
+    }
+
+    /// Reads the selected option id as an enum, using the variant names like serde does
+    pub fn get_enum<T: DeserializeOwned>(&self) -> Option<T> {
+        serde_json::from_value(Value::String(self.setting_value.to_owned())).ok()
+    }
+
+    /// Reads a json array of strings, or a list separated by commas or new lines
+    pub fn get_list(&self) -> Vec<String> {
+        let value = self.setting_value.trim();
+
+        if value.starts_with('[') {
+            if let Ok(list) = serde_json::from_str::<Vec<String>>(value) {
+                return list;
+            }
+        }
+
+        value
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+/// Reads `true`/`false`, `1`/`0`, `yes`/`no` and `on`/`off`
+pub fn parse_bool(value: impl AsRef<str>) -> Option<bool> {
+    match value.as_ref().trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
 fn default_first_key() -> String {
     String::from("ctrl")
 }
@@ -314,28 +421,550 @@ pub fn get_default_settings() -> Settings {
     }
 }
 
+/// The formats a settings file can be stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsFormat {
+    Json,
+    /// The bincode format of older versions
+    Binary,
+}
+
+impl SettingsFormat {
+    /// Detects the format of the file content.
+    ///
+    /// JSON settings are an object, while bincode settings start with the length of the first key.
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'{') => SettingsFormat::Json,
+            _ => SettingsFormat::Binary,
+        }
+    }
+}
+
+/// Gets the settings, or the default settings if the settings file can't be decoded.
+///
+/// An unreadable settings file is backed up before falling back, so it isn't lost on the next write.
 pub fn get_settings() -> Settings {
-    let settings_path = get_settings_path();
+    match try_get_settings() {
+        Ok(settings) => settings,
+        Err(Error::Bincode(_)) | Err(Error::Json(_)) => {
+            if let Err(error) = backup_unreadable_settings(&get_settings_path()) {
+                eprintln!("Error backing up settings: {error}");
+            }
 
-    if !settings_path.parent().unwrap().exists() {
-        fs::create_dir_all(&settings_path.parent().unwrap())
-            .expect("Error creating settings directory");
+            get_default_settings()
+        }
+        Err(error) => panic!("Error reading settings: {error}"),
     }
+}
+
+/// Gets the settings. On the first read, the settings of `settings.bin` are migrated to the settings file.
+///
+/// Settings of older versions are upgraded and written back, keeping a backup of the old file.
+pub fn try_get_settings() -> Result<Settings> {
+    let settings_path = get_settings_path();
 
     if !settings_path.exists() {
-        fs::write(
-            &settings_path,
-            bincode::serialize(&get_default_settings()).expect("Error serializing settings"),
-        )
-        .expect("Error writing settings");
+        let _lock = lock_settings()?;
+
+        // The launcher and the companion start together, so the other one may have migrated them while waiting
+        if !settings_path.exists() {
+            migrate_legacy_settings()?;
+        }
     }
 
-    let settings_bytes = fs::read(get_settings_path()).expect("Error reading settings");
-    let decoded_settings = bincode::deserialize::<Settings>(&settings_bytes);
+    let bytes =
+        fs::read(&settings_path).map_err(|error| Error::from_read(error, &settings_path))?;
+    let (version, settings) = decode_settings_version(&bytes)?;
 
-    match decoded_settings {
-        Ok(settings) => settings,
-        Err(_) => get_default_settings(),
+    if version < SETTINGS_VERSION {
+        let backup_path = get_settings_backup_path(&settings_path, &format!("v{version}"));
+
+        fs::copy(&settings_path, backup_path)?;
+        write_settings_file(&settings)?;
+    }
+
+    Ok(settings)
+}
+
+/// The version of the settings layout, stored in the `version` key of the settings file.
+///
+/// Files without the key were written before it existed and have the layout of version 2.
+pub const SETTINGS_VERSION: u32 = 2;
+
+/// The migrations between settings versions, in order. The first one upgrades version 1 to version 2.
+const SETTINGS_MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_settings_v1];
+
+/// Adds the grid and app icon settings
+fn migrate_settings_v1(settings: &mut Map<String, Value>) {
+    settings
+        .entry("show_apps_as_grid")
+        .or_insert(Value::Bool(default_show_apps_as_grid()));
+
+    settings
+        .entry("hide_app_icons")
+        .or_insert(Value::Bool(default_hide_app_icons()));
+}
+
+/// Decodes the settings in any of the [`SettingsFormat`] and version
+pub fn decode_settings(bytes: &[u8]) -> Result<Settings> {
+    Ok(decode_settings_version(bytes)?.1)
+}
+
+/// Decodes the settings, upgrading them to the current version. Also returns the version they were stored in.
+fn decode_settings_version(bytes: &[u8]) -> Result<(u32, Settings)> {
+    let (version, mut settings) = match SettingsFormat::detect(bytes) {
+        SettingsFormat::Json => {
+            let settings: Map<String, Value> = serde_json::from_slice(bytes)?;
+            let version = get_settings_version(settings.get("version"));
+
+            (version, settings)
+        }
+        SettingsFormat::Binary => match bincode::deserialize::<Settings>(bytes) {
+            Ok(settings) => (SETTINGS_VERSION, to_settings_map(&settings)?),
+            Err(_) => (
+                1,
+                to_settings_map(&bincode::deserialize::<SettingsV1>(bytes)?)?,
+            ),
+        },
+    };
+
+    let first_migration = (version as usize).saturating_sub(1);
+
+    for migration in SETTINGS_MIGRATIONS.iter().skip(first_migration) {
+        migration(&mut settings);
+    }
+
+    Ok((version, serde_json::from_value(Value::Object(settings))?))
+}
+
+/// The version in the `version` key of a settings file
+fn get_settings_version(version: Option<&Value>) -> u32 {
+    match version {
+        Some(version) => {
+            // Versions that don't fit are from a newer version, not from an older layout
+            let version = version.as_u64().unwrap_or_default();
+            u32::try_from(version).unwrap_or(u32::MAX)
+        }
+        None => 2,
+    }
+}
+
+fn to_settings_map(settings: &impl Serialize) -> Result<Map<String, Value>> {
+    match serde_json::to_value(settings)? {
+        Value::Object(settings) => Ok(settings),
+        _ => unreachable!("Settings are serialized as an object"),
+    }
+}
+
+/// A copy of the settings file next to it, like `settings.json.v1.1700000000.bak`
+fn get_settings_backup_path(settings_path: &Path, reason: &str) -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let mut file_name = get_settings_backup_prefix(settings_path, reason);
+    file_name.push_str(&format!("{seconds}.bak"));
+
+    settings_path.with_file_name(file_name)
+}
+
+/// The start of the backup file names of a reason, like `settings.json.unreadable.`
+fn get_settings_backup_prefix(settings_path: &Path, reason: &str) -> String {
+    let file_name = settings_path.file_name().unwrap_or_default();
+    format!("{}.{reason}.", file_name.to_string_lossy())
+}
+
+/// Backs up a settings file that can't be decoded. The settings are read all the time, so the file is only backed up
+/// when no backup has the same content.
+fn backup_unreadable_settings(settings_path: &Path) -> io::Result<()> {
+    let bytes = fs::read(settings_path)?;
+    let prefix = get_settings_backup_prefix(settings_path, "unreadable");
+
+    if let Some(settings_dir) = settings_path.parent() {
+        let is_backed_up = fs::read_dir(settings_dir)?.flatten().any(|entry| {
+            entry.file_name().to_string_lossy().starts_with(&prefix)
+                && fs::read(entry.path()).is_ok_and(|backup| backup == bytes)
+        });
+
+        if is_backed_up {
+            return Ok(());
+        }
+    }
+
+    write_file_atomic(get_settings_backup_path(settings_path, "unreadable"), bytes)
+}
+
+/// Gets how many times the settings were written. It's 0 before the first write.
+pub fn get_settings_generation() -> u64 {
+    fs::read_to_string(get_settings_generation_path())
+        .ok()
+        .and_then(|generation| generation.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+/// A change of the settings, seen by a running process
+#[derive(Debug, Clone)]
+pub struct SettingsChange {
+    /// The generation of the new settings. See [`get_settings_generation`].
+    pub generation: u64,
+    pub old: Settings,
+    pub new: Settings,
+    /// The keys of the settings that changed, like `theme` or `first_key`
+    pub changed: Vec<String>,
+}
+
+impl SettingsChange {
+    pub fn new(generation: u64, old: Settings, new: Settings) -> Self {
+        let changed = diff_settings(&old, &new);
+
+        Self {
+            generation,
+            old,
+            new,
+            changed,
+        }
+    }
+
+    pub fn has_changed(&self, key: impl AsRef<str>) -> bool {
+        self.changed.iter().any(|changed| changed == key.as_ref())
+    }
+}
+
+/// Gets the keys of the settings that are different, sorted by name
+pub fn diff_settings(old: &Settings, new: &Settings) -> Vec<String> {
+    let (Ok(old), Ok(new)) = (to_settings_map(old), to_settings_map(new)) else {
+        return Vec::new();
+    };
+
+    new.into_iter()
+        .filter(|(key, value)| old.get(key) != Some(value))
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// How imported settings are combined with the current settings
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The imported settings replace the current settings
+    Replace,
+    /// The imported settings win, and the search engines, blacklist and extension settings are combined
+    Merge,
+    /// The current settings win, and only the missing search engines, blacklist and extension settings are added
+    KeepExisting,
+}
+
+/// The settings file inside a settings bundle
+const BUNDLE_SETTINGS_FILE: &str = "settings.json";
+
+/// Packages the settings into a zip bundle that can be imported in another machine.
+///
+/// The wallpaper and search engine icons are included, and their paths are made relative to the bundle. Secret
+/// settings aren't part of the settings, so they're never exported.
+pub fn export_settings(path: impl AsRef<Path>) -> Result<()> {
+    let mut settings = get_settings();
+    let mut files = Vec::<(String, PathBuf)>::new();
+
+    if let Some(wallpaper) = settings.wallpaper.to_owned() {
+        if let Some(name) = get_bundle_file_name("wallpaper", &wallpaper) {
+            files.push((name.to_owned(), PathBuf::from(wallpaper)));
+            settings.wallpaper = Some(name);
+        }
+    }
+
+    for search_engine in &mut settings.search_engines {
+        if let Some(icon_path) = search_engine.icon_path.to_owned() {
+            let dir = format!("search-engines/{}", search_engine.id);
+
+            if let Some(name) = get_bundle_file_name(&dir, &icon_path) {
+                files.push((name.to_owned(), PathBuf::from(icon_path)));
+                search_engine.icon_path = Some(name);
+            }
+        }
+    }
+
+    let mut value = to_settings_map(&settings)?;
+    value.insert("version".to_owned(), Value::from(SETTINGS_VERSION));
+
+    let options = SimpleFileOptions::default();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    writer.start_file(BUNDLE_SETTINGS_FILE, options)?;
+    writer.write_all(&serde_json::to_vec_pretty(&value)?)?;
+
+    for (name, file_path) in files {
+        let bytes = fs::read(&file_path).map_err(|error| Error::from_read(error, file_path))?;
+
+        writer.start_file(name, options)?;
+        writer.write_all(&bytes)?;
+    }
+
+    write_file_atomic(path, writer.finish()?.into_inner())?;
+
+    Ok(())
+}
+
+/// Imports a bundle made by [`export_settings`] and saves the result. Returns the saved settings.
+///
+/// The bundled files are extracted to the imported settings files directory and their paths are made absolute again.
+pub fn import_settings(path: impl AsRef<Path>, merge_strategy: MergeStrategy) -> Result<Settings> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|error| Error::from_read(error, path))?;
+    let mut archive = ZipArchive::new(file)?;
+
+    let mut bytes = Vec::new();
+    archive
+        .by_name(BUNDLE_SETTINGS_FILE)
+        .map_err(|_| Error::UnsupportedArchive(path.to_owned()))?
+        .read_to_end(&mut bytes)?;
+
+    let mut imported = decode_settings(&bytes)?;
+    let files_dir = get_imported_settings_files_dir();
+
+    if let Some(wallpaper) = &imported.wallpaper {
+        imported.wallpaper = Some(extract_bundle_file(&mut archive, wallpaper, &files_dir)?);
+    }
+
+    for search_engine in &mut imported.search_engines {
+        if let Some(icon_path) = &search_engine.icon_path {
+            search_engine.icon_path =
+                Some(extract_bundle_file(&mut archive, icon_path, &files_dir)?);
+        }
+    }
+
+    update_settings(|settings| {
+        *settings = merge_settings(settings.to_owned(), imported, merge_strategy);
+        settings.to_owned()
+    })
+}
+
+/// The path of a file in a bundle, like `files/wallpaper/forest.png`. Files that don't exist aren't bundled.
+fn get_bundle_file_name(dir: &str, path: &str) -> Option<String> {
+    let path = Path::new(path);
+
+    if !path.is_file() {
+        return None;
+    }
+
+    let file_name = path.file_name()?.to_str()?;
+    Some(format!("files/{dir}/{file_name}"))
+}
+
+/// Extracts the file if it's in the bundle and gets its path. Paths that aren't in the bundle are kept.
+fn extract_bundle_file(
+    archive: &mut ZipArchive<File>,
+    name: &str,
+    files_dir: &Path,
+) -> Result<String> {
+    let Ok(mut file) = archive.by_name(name) else {
+        return Ok(name.to_owned());
+    };
+
+    // Names that would be extracted outside the directory are kept as they are
+    let Some(relative_path) = file.enclosed_name() else {
+        return Ok(name.to_owned());
+    };
+
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)?;
+
+    let target_path = files_dir.join(relative_path);
+    write_file_atomic(&target_path, bytes)?;
+
+    Ok(target_path.to_string_lossy().into_owned())
+}
+
+/// Combines the imported settings with the current settings. See [`MergeStrategy`].
+///
+/// Search engines are matched by keyword and extension settings by extension and setting id.
+fn merge_settings(
+    current: Settings,
+    imported: Settings,
+    merge_strategy: MergeStrategy,
+) -> Settings {
+    let prefer_imported = match merge_strategy {
+        MergeStrategy::Replace => return imported,
+        MergeStrategy::Merge => true,
+        MergeStrategy::KeepExisting => false,
+    };
+
+    let mut merged = match prefer_imported {
+        true => imported.to_owned(),
+        false => current.to_owned(),
+    };
+
+    merged.blacklist = current.blacklist;
+
+    for entry in imported.blacklist {
+        if !merged.blacklist.contains(&entry) {
+            merged.blacklist.push(entry);
+        }
+    }
+
+    merged.extensions = current.extensions;
+
+    for setting in imported.extensions {
+        let existing = merged
+            .extensions
+            .iter_mut()
+            .find(|s| s.extension_id == setting.extension_id && s.setting_id == setting.setting_id);
+
+        match existing {
+            Some(existing) if prefer_imported => existing.setting_value = setting.setting_value,
+            Some(_) => {}
+            None => merged.extensions.push(setting),
+        }
+    }
+
+    merged.search_engines = current.search_engines;
+    merged.default_search_engine = current.default_search_engine;
+
+    for search_engine in imported.search_engines {
+        let imported_id = search_engine.id;
+        let existing = merged
+            .search_engines
+            .iter_mut()
+            .find(|s| s.keyword == search_engine.keyword);
+
+        let id = match existing {
+            Some(existing) => {
+                if prefer_imported {
+                    *existing = SearchEngine {
+                        id: existing.id,
+                        ..search_engine
+                    };
+                }
+
+                existing.id
+            }
+            None => {
+                let id = merged
+                    .search_engines
+                    .iter()
+                    .map(|s| s.id + 1)
+                    .max()
+                    .unwrap_or_default();
+
+                merged.search_engines.push(SearchEngine {
+                    id,
+                    ..search_engine
+                });
+
+                id
+            }
+        };
+
+        if prefer_imported && imported_id == imported.default_search_engine {
+            merged.default_search_engine = id;
+        }
+    }
+
+    merged
+}
+
+/// Writes the settings of `settings.bin`, or the default settings, to the settings file.
+///
+/// The old file is kept as `settings.bin.bak` so the migration only happens once, even when it can't be decoded.
+fn migrate_legacy_settings() -> Result<()> {
+    let legacy_path = get_legacy_settings_path();
+
+    if !legacy_path.exists() {
+        return write_settings_file(&get_default_settings());
+    }
+
+    let bytes = fs::read(&legacy_path)?;
+    let settings = decode_settings(&bytes).unwrap_or_else(|_| get_default_settings());
+
+    write_settings_file(&settings)?;
+    fs::rename(&legacy_path, legacy_path.with_extension("bin.bak"))?;
+
+    Ok(())
+}
+
+/// Writes the settings as JSON.
+///
+/// Keys in the file that aren't settings, like the ones of newer versions or added by hand, are kept.
+fn write_settings_file(settings: &Settings) -> Result<()> {
+    let settings_path = get_settings_path();
+
+    if let Some(parent) = settings_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // Keeps the keys of a write from another process between the read and the write
+    let _lock = lock_settings()?;
+    let mut value = to_settings_map(settings)?;
+    value.insert("version".to_owned(), Value::from(SETTINGS_VERSION));
+
+    if let Some(Value::Object(current_keys)) = read_settings_value(&settings_path) {
+        keep_current_keys(&mut value, current_keys);
+    }
+
+    write_file_atomic(&settings_path, serde_json::to_vec_pretty(&value)?)?;
+
+    let generation = get_settings_generation() + 1;
+    write_file_atomic(get_settings_generation_path(), generation.to_string())?;
+
+    Ok(())
+}
+
+/// Adds the keys of the settings file that the written settings don't have.
+///
+/// A file from a newer version keeps its version, so the newer launcher doesn't take it for an older layout.
+fn keep_current_keys(value: &mut Map<String, Value>, current_keys: Map<String, Value>) {
+    for (key, current_value) in current_keys {
+        if key == "version" {
+            if get_settings_version(Some(&current_value)) > SETTINGS_VERSION {
+                value.insert(key, current_value);
+            }
+
+            continue;
+        }
+
+        value.entry(key).or_insert(current_value);
+    }
+}
+
+thread_local! {
+    /// How many settings locks the thread holds. Only the first one locks the file.
+    static SETTINGS_LOCK_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A lock on the settings file, released when dropped
+struct SettingsLock {
+    _lock: Option<FileLock>,
+}
+
+impl Drop for SettingsLock {
+    fn drop(&mut self) {
+        SETTINGS_LOCK_DEPTH.set(SETTINGS_LOCK_DEPTH.get() - 1);
+    }
+}
+
+/// Waits until no other process uses the settings file.
+///
+/// Locking again from a thread that already holds the lock doesn't wait, so locked functions can call each other.
+fn lock_settings() -> Result<SettingsLock> {
+    let depth = SETTINGS_LOCK_DEPTH.get();
+
+    let lock = match depth {
+        0 => Some(lock_file(get_settings_path())?),
+        _ => None,
+    };
+
+    SETTINGS_LOCK_DEPTH.set(depth + 1);
+
+    Ok(SettingsLock { _lock: lock })
+}
+
+/// Reads the settings file as a JSON value, if it's a JSON file
+fn read_settings_value(path: &Path) -> Option<Value> {
+    let bytes = fs::read(path).ok()?;
+
+    match SettingsFormat::detect(&bytes) {
+        SettingsFormat::Json => serde_json::from_slice(&bytes).ok(),
+        SettingsFormat::Binary => None,
     }
 }
 
@@ -364,7 +993,7 @@ Exec=whiskers-launcher-companion index-apps"#;
             desktop_file_path.push("whiskers-launcher.desktop");
 
             if settings.auto_start {
-                fs::write(&desktop_file_path, &desktop_content)
+                fs::write(&desktop_file_path, desktop_content)
                     .map_err(|_| ())
                     .unwrap();
 
@@ -399,6 +1028,409 @@ Exec=whiskers-launcher-companion index-apps"#;
         }
     }
 
-    let bytes = bincode::serialize(&settings).expect("Error serializing settings");
-    fs::write(&get_settings_path(), bytes).expect("Error writing settings");
+    write_settings_file(&settings).expect("Error writing settings");
+}
+
+/// Reads the settings, changes them and writes them back, holding the settings lock the whole time. Returns what the
+/// update returns.
+///
+/// The launcher and the companion run at the same time, so a change made by the other one between reading and
+/// writing the settings would otherwise be lost.
+pub fn update_settings<T>(update: impl FnOnce(&mut Settings) -> T) -> Result<T> {
+    let _lock = lock_settings()?;
+    let mut settings = get_settings();
+    let value = update(&mut settings);
+
+    write_settings(settings);
+
+    Ok(value)
+}
+
+/// Validates the value against the extension manifest and saves it. Returns the value as it was stored.
+///
+/// Password and secret settings are saved with the secrets instead of in the settings.
+pub fn set_extension_setting(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+    setting_value: impl Into<String>,
+) -> Result<String> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+    let manifest_setting = get_manifest_setting(&extension_id, &setting_id)?;
+
+    let setting_value = match &manifest_setting {
+        Some(manifest_setting) => manifest_setting.validate_value(setting_value.into())?,
+        None => setting_value.into(),
+    };
+
+    let is_secret = manifest_setting
+        .as_ref()
+        .is_some_and(|setting| setting.setting_type.is_secret());
+
+    let is_setting =
+        |s: &ExtensionSetting| s.extension_id == extension_id && s.setting_id == setting_id;
+
+    if is_secret {
+        set_secret(&extension_id, &setting_id, &setting_value)?;
+
+        if get_settings().extensions.iter().any(is_setting) {
+            update_settings(|settings| settings.extensions.retain(|s| !is_setting(s)))?;
+        }
+
+        return Ok(setting_value);
+    }
+
+    update_settings(|settings| {
+        let setting = settings.extensions.iter_mut().find(|s| is_setting(s));
+
+        match setting {
+            Some(setting) => setting.setting_value = setting_value.to_owned(),
+            None => settings.extensions.push(ExtensionSetting {
+                extension_id: extension_id.to_owned(),
+                setting_id: setting_id.to_owned(),
+                setting_value: setting_value.to_owned(),
+            }),
+        }
+    })?;
+
+    Ok(setting_value)
+}
+
+/// Checks the value against the setting declared in the extension manifest.
+///
+/// Returns the value in the form it should be stored in. The keyword can always be set.
+pub fn validate_extension_setting(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+    setting_value: impl Into<String>,
+) -> Result<String> {
+    let extension_id = extension_id.into();
+    let setting_id = setting_id.into();
+
+    match get_manifest_setting(&extension_id, &setting_id)? {
+        Some(setting) => setting.validate_value(setting_value.into()),
+        None => Ok(setting_value.into()),
+    }
+}
+
+/// Checks if the setting is stored with the secrets instead of in the settings
+pub fn is_secret_setting(extension_id: impl Into<String>, setting_id: impl Into<String>) -> bool {
+    matches!(
+        get_manifest_setting(&extension_id.into(), &setting_id.into()),
+        Ok(Some(setting)) if setting.setting_type.is_secret()
+    )
+}
+
+/// The setting declared in the extension manifest. The keyword isn't declared, so it's `None`.
+fn get_manifest_setting(
+    extension_id: &str,
+    setting_id: &str,
+) -> Result<Option<ExtensionManifestSetting>> {
+    if setting_id == "keyword" {
+        return Ok(None);
+    }
+
+    let extension = get_indexed_extensions()?
+        .into_iter()
+        .find(|extension| extension.id == extension_id)
+        .ok_or_else(|| Error::ExtensionNotFound(extension_id.to_owned()))?;
+
+    extension
+        .settings
+        .into_iter()
+        .flatten()
+        .find(|setting| setting.id == setting_id)
+        .map(Some)
+        .ok_or_else(|| Error::UnknownSetting(setting_id.to_owned()))
+}
+
+/// The indexed extensions. There are none before the extensions are indexed for the first time.
+fn get_indexed_extensions() -> Result<Vec<ExtensionManifest>> {
+    match try_get_extensions() {
+        Ok(extensions) => Ok(extensions),
+        Err(Error::MissingFile(_)) | Err(Error::Bincode(_)) => Ok(Vec::new()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Checks every extension setting against the extension manifests. Settings of extensions that aren't installed are
+/// ignored.
+pub fn validate_extension_settings(settings: &Settings) -> Result<()> {
+    let extensions = get_indexed_extensions()?;
+
+    for extension_setting in &settings.extensions {
+        let extension = extensions
+            .iter()
+            .find(|extension| extension.id == extension_setting.extension_id);
+
+        let manifest_setting = extension
+            .and_then(|extension| extension.settings.as_ref())
+            .and_then(|manifest_settings| {
+                manifest_settings
+                    .iter()
+                    .find(|setting| setting.id == extension_setting.setting_id)
+            });
+
+        if let Some(manifest_setting) = manifest_setting {
+            manifest_setting.validate_value(&extension_setting.setting_value)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_settings_map() -> Map<String, Value> {
+        let mut settings = get_default_settings();
+        settings.first_key = String::from("alt");
+        settings.show_apps_as_grid = true;
+
+        to_settings_map(&settings).unwrap()
+    }
+
+    #[test]
+    fn detect_tells_json_from_bincode() {
+        assert_eq!(SettingsFormat::detect(b"  {}"), SettingsFormat::Json);
+
+        let bytes = bincode::serialize(&get_default_settings()).unwrap();
+        assert_eq!(SettingsFormat::detect(&bytes), SettingsFormat::Binary);
+    }
+
+    #[test]
+    fn version_1_bincode_settings_are_migrated() {
+        let mut settings = get_settings_map();
+        settings.remove("show_apps_as_grid");
+        settings.remove("hide_app_icons");
+
+        let settings_v1: SettingsV1 = serde_json::from_value(Value::Object(settings)).unwrap();
+        let bytes = bincode::serialize(&settings_v1).unwrap();
+        let (version, settings) = decode_settings_version(&bytes).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(settings.first_key, "alt");
+        assert_eq!(settings.show_apps_as_grid, default_show_apps_as_grid());
+        assert_eq!(settings.hide_app_icons, default_hide_app_icons());
+    }
+
+    #[test]
+    fn version_2_bincode_settings_are_kept() {
+        let settings: Settings = serde_json::from_value(Value::Object(get_settings_map())).unwrap();
+        let bytes = bincode::serialize(&settings).unwrap();
+        let (version, settings) = decode_settings_version(&bytes).unwrap();
+
+        assert_eq!(version, 2);
+        assert!(settings.show_apps_as_grid);
+    }
+
+    #[test]
+    fn json_settings_are_migrated_from_their_version() {
+        let mut settings = get_settings_map();
+        settings.remove("show_apps_as_grid");
+        settings.insert("version".to_owned(), Value::from(1));
+
+        let bytes = serde_json::to_vec(&settings).unwrap();
+        let (version, settings) = decode_settings_version(&bytes).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(settings.first_key, "alt");
+        assert_eq!(settings.show_apps_as_grid, default_show_apps_as_grid());
+    }
+
+    #[test]
+    fn every_migration_adds_the_keys_of_the_next_version() {
+        let mut settings = get_settings_map();
+        settings.remove("show_apps_as_grid");
+        settings.remove("hide_app_icons");
+
+        for migration in SETTINGS_MIGRATIONS {
+            migration(&mut settings);
+        }
+
+        assert_eq!(SETTINGS_MIGRATIONS.len() as u32, SETTINGS_VERSION - 1);
+        assert_eq!(settings["show_apps_as_grid"], Value::Bool(false));
+        assert_eq!(settings["hide_app_icons"], Value::Bool(false));
+    }
+
+    #[test]
+    fn json_settings_without_a_version_are_version_2() {
+        let bytes = serde_json::to_vec(&get_settings_map()).unwrap();
+        let (version, settings) = decode_settings_version(&bytes).unwrap();
+
+        assert_eq!(version, 2);
+        assert!(settings.show_apps_as_grid);
+    }
+
+    #[test]
+    fn versions_that_dont_fit_are_newer_versions() {
+        let mut settings = get_settings_map();
+        settings.insert("version".to_owned(), Value::from(u64::from(u32::MAX) + 2));
+
+        let bytes = serde_json::to_vec(&settings).unwrap();
+        let (version, settings) = decode_settings_version(&bytes).unwrap();
+
+        assert_eq!(version, u32::MAX);
+        assert!(settings.show_apps_as_grid);
+    }
+
+    #[test]
+    fn writes_keep_the_keys_and_the_version_of_newer_files() {
+        let mut current_keys = get_settings_map();
+        current_keys.insert("version".to_owned(), Value::from(SETTINGS_VERSION + 1));
+        current_keys.insert("newer_key".to_owned(), Value::Bool(true));
+
+        let mut value = get_settings_map();
+        value.insert("version".to_owned(), Value::from(SETTINGS_VERSION));
+        keep_current_keys(&mut value, current_keys);
+
+        assert_eq!(value["version"], Value::from(SETTINGS_VERSION + 1));
+        assert_eq!(value["newer_key"], Value::Bool(true));
+    }
+
+    #[test]
+    fn writes_upgrade_the_version_of_older_files() {
+        let mut current_keys = get_settings_map();
+        current_keys.insert("version".to_owned(), Value::from(1));
+
+        let mut value = get_settings_map();
+        value.insert("version".to_owned(), Value::from(SETTINGS_VERSION));
+        keep_current_keys(&mut value, current_keys);
+
+        assert_eq!(value["version"], Value::from(SETTINGS_VERSION));
+    }
+
+    fn get_search_engine(id: usize, keyword: &str, name: &str) -> SearchEngine {
+        SearchEngine {
+            id,
+            icon_path: None,
+            tint_icon: false,
+            keyword: keyword.to_owned(),
+            name: name.to_owned(),
+            search_query: format!("https://{keyword}.example.com/?q=%s"),
+        }
+    }
+
+    fn get_extension_setting(setting_id: &str, setting_value: &str) -> ExtensionSetting {
+        ExtensionSetting {
+            extension_id: String::from("weather"),
+            setting_id: setting_id.to_owned(),
+            setting_value: setting_value.to_owned(),
+        }
+    }
+
+    /// Settings that share the `gs` search engine, the `ads` blacklist entry and the `unit` extension setting
+    fn get_merge_settings() -> (Settings, Settings) {
+        let mut current = get_default_settings();
+        current.first_key = String::from("ctrl");
+        current.blacklist = vec![String::from("ads"), String::from("games")];
+        current.search_engines = vec![
+            get_search_engine(0, "gs", "Google"),
+            get_search_engine(1, "br", "Brave"),
+        ];
+        current.default_search_engine = 1;
+        current.extensions = vec![
+            get_extension_setting("unit", "celsius"),
+            get_extension_setting("city", "berlin"),
+        ];
+
+        let mut imported = get_default_settings();
+        imported.first_key = String::from("alt");
+        imported.blacklist = vec![String::from("ads"), String::from("music")];
+        imported.search_engines = vec![
+            get_search_engine(0, "ddg", "DuckDuckGo"),
+            get_search_engine(1, "gs", "Google Search"),
+        ];
+        imported.default_search_engine = 0;
+        imported.extensions = vec![
+            get_extension_setting("unit", "fahrenheit"),
+            get_extension_setting("days", "7"),
+        ];
+
+        (current, imported)
+    }
+
+    fn get_value<'a>(settings: &'a Settings, setting_id: &str) -> Option<&'a str> {
+        settings
+            .extensions
+            .iter()
+            .find(|setting| setting.setting_id == setting_id)
+            .map(|setting| setting.setting_value.as_str())
+    }
+
+    #[test]
+    fn integers_can_be_read_from_floats() {
+        assert_eq!(get_extension_setting("count", "3.0").get_i64(), Some(3));
+        assert_eq!(get_extension_setting("count", " 42 ").get_i64(), Some(42));
+        assert_eq!(get_extension_setting("count", "3.5").get_i64(), None);
+        assert_eq!(get_extension_setting("count", "three").get_i64(), None);
+    }
+
+    #[test]
+    fn lists_are_read_from_json_or_separated_values() {
+        let list = vec![String::from("a, b"), String::from("c")];
+
+        assert_eq!(
+            get_extension_setting("list", r#"["a, b", "c"]"#).get_list(),
+            list
+        );
+        assert_eq!(
+            get_extension_setting("list", "a,\nb , ,c").get_list(),
+            vec!["a", "b", "c"]
+        );
+        assert!(get_extension_setting("list", "").get_list().is_empty());
+    }
+
+    #[test]
+    fn replace_uses_the_imported_settings() {
+        let (current, imported) = get_merge_settings();
+        let merged = merge_settings(current, imported, MergeStrategy::Replace);
+
+        assert_eq!(merged.first_key, "alt");
+        assert_eq!(merged.blacklist, vec!["ads", "music"]);
+        assert_eq!(merged.search_engines.len(), 2);
+        assert_eq!(get_value(&merged, "city"), None);
+    }
+
+    #[test]
+    fn merge_prefers_the_imported_settings_and_combines_the_lists() {
+        let (current, imported) = get_merge_settings();
+        let merged = merge_settings(current, imported, MergeStrategy::Merge);
+
+        assert_eq!(merged.first_key, "alt");
+        assert_eq!(merged.blacklist, vec!["ads", "games", "music"]);
+
+        assert_eq!(get_value(&merged, "unit"), Some("fahrenheit"));
+        assert_eq!(get_value(&merged, "city"), Some("berlin"));
+        assert_eq!(get_value(&merged, "days"), Some("7"));
+
+        // Search engines are matched by keyword and keep the current id
+        let google = merged.search_engines.iter().find(|s| s.keyword == "gs");
+        assert_eq!(
+            google.map(|s| (s.id, s.name.as_str())),
+            Some((0, "Google Search"))
+        );
+
+        let duckduckgo = merged.search_engines.iter().find(|s| s.keyword == "ddg");
+        assert_eq!(duckduckgo.map(|s| s.id), Some(2));
+        assert_eq!(merged.default_search_engine, 2);
+    }
+
+    #[test]
+    fn keep_existing_only_adds_what_is_missing() {
+        let (current, imported) = get_merge_settings();
+        let merged = merge_settings(current, imported, MergeStrategy::KeepExisting);
+
+        assert_eq!(merged.first_key, "ctrl");
+        assert_eq!(merged.blacklist, vec!["ads", "games", "music"]);
+
+        assert_eq!(get_value(&merged, "unit"), Some("celsius"));
+        assert_eq!(get_value(&merged, "days"), Some("7"));
+
+        let google = merged.search_engines.iter().find(|s| s.keyword == "gs");
+        assert_eq!(google.map(|s| s.name.as_str()), Some("Google"));
+        assert_eq!(merged.search_engines.len(), 3);
+        assert_eq!(merged.default_search_engine, 1);
+    }
 }
@@ -0,0 +1,406 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    features::extensions::ExtensionIndexEntry,
+    paths::{get_extensions_store_path, get_stores_cache_dir, get_themes_store_path},
+    utils::write_file_atomic,
+};
+
+/// How long a cached store is used before it should be fetched again
+pub const DEFAULT_STORE_EXPIRY: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// The json index of a store.
+///
+/// Stores are plain files, so a private store can be hosted on any web server or file share.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreCatalog {
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<StoreExtension>,
+    #[serde(default = "default_themes")]
+    pub themes: Vec<StoreTheme>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreExtension {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_string")]
+    pub description: String,
+    /// The semantic version of the extension, like `1.2.0`
+    pub version: String,
+    #[serde(default = "default_optional_string")]
+    pub author: Option<String>,
+    /// A `.tar.gz` or `.zip` archive that can be given to [`crate::features::core::extensions::install_extension`]
+    pub download_url: String,
+    /// The sha256 of the archive, in hex
+    #[serde(default = "default_optional_string")]
+    pub checksum: Option<String>,
+    /// An image showing the extension
+    #[serde(default = "default_optional_string")]
+    pub preview: Option<String>,
+    #[serde(default = "default_os")]
+    pub os: String,
+    #[serde(default = "default_tags")]
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreTheme {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_string")]
+    pub description: String,
+    pub version: String,
+    #[serde(default = "default_optional_string")]
+    pub author: Option<String>,
+    pub download_url: String,
+    /// The sha256 of the theme file, in hex
+    #[serde(default = "default_optional_string")]
+    pub checksum: Option<String>,
+    #[serde(default = "default_optional_string")]
+    pub preview: Option<String>,
+    #[serde(default = "default_tags")]
+    pub tags: Vec<String>,
+}
+
+/// A store saved in the stores cache
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoreCache<T> {
+    /// Where the store was loaded from
+    pub source: String,
+    /// When the store was loaded, in seconds since the unix epoch
+    pub fetched_at: u64,
+    pub items: Vec<T>,
+}
+
+impl StoreCatalog {
+    pub fn parse(json: impl AsRef<str>) -> Result<Self> {
+        Ok(serde_json::from_str(json.as_ref())?)
+    }
+
+    /// Loads a catalog from a local file, like a private store on a file share
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = fs::read_to_string(path).map_err(|error| Error::from_read(error, path))?;
+
+        Self::parse(json)
+    }
+}
+
+impl StoreExtension {
+    /// Checks if the store version is newer than the installed one
+    pub fn is_update_for(&self, installed: &ExtensionIndexEntry) -> bool {
+        installed.manifest.id == self.id && installed.manifest.is_outdated(&self.version)
+    }
+
+    /// Checks if the downloaded archive matches the checksum. Extensions without a checksum always match.
+    pub fn verify_download(&self, path: impl AsRef<Path>) -> Result<bool> {
+        verify_checksum(path.as_ref(), &self.checksum)
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        matches_query(
+            query,
+            [&self.id, &self.name, &self.description]
+                .into_iter()
+                .chain(&self.author)
+                .chain(&self.tags),
+        )
+    }
+}
+
+impl StoreTheme {
+    /// Checks if the downloaded theme matches the checksum. Themes without a checksum always match.
+    pub fn verify_download(&self, path: impl AsRef<Path>) -> Result<bool> {
+        verify_checksum(path.as_ref(), &self.checksum)
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        matches_query(
+            query,
+            [&self.id, &self.name, &self.description]
+                .into_iter()
+                .chain(&self.author)
+                .chain(&self.tags),
+        )
+    }
+}
+
+impl<T> StoreCache<T> {
+    pub fn new(source: impl Into<String>, items: Vec<T>) -> Self {
+        Self {
+            source: source.into(),
+            fetched_at: get_unix_time(),
+            items,
+        }
+    }
+
+    /// Checks if the store was loaded longer than the max age ago
+    pub fn is_expired(&self, max_age: Duration) -> bool {
+        get_unix_time().saturating_sub(self.fetched_at) >= max_age.as_secs()
+    }
+}
+
+/// Saves the catalog in the stores cache
+pub fn write_store_cache(catalog: StoreCatalog, source: impl Into<String>) -> Result<()> {
+    let source = source.into();
+
+    fs::create_dir_all(get_stores_cache_dir())?;
+
+    let extensions = StoreCache::new(&source, catalog.extensions);
+    write_file_atomic(
+        get_extensions_store_path(),
+        bincode::serialize(&extensions)?,
+    )?;
+
+    let themes = StoreCache::new(&source, catalog.themes);
+    write_file_atomic(get_themes_store_path(), bincode::serialize(&themes)?)?;
+
+    Ok(())
+}
+
+/// Loads the catalog from a local file and saves it in the stores cache
+pub fn refresh_store_cache_from_file(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let catalog = StoreCatalog::from_file(path)?;
+
+    write_store_cache(catalog, path.display().to_string())
+}
+
+/// Gets the cached extensions store. Returns `None` when the store was never cached.
+pub fn get_extensions_store() -> Option<StoreCache<StoreExtension>> {
+    let bytes = fs::read(get_extensions_store_path()).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+/// Gets the cached themes store. Returns `None` when the store was never cached.
+pub fn get_themes_store() -> Option<StoreCache<StoreTheme>> {
+    let bytes = fs::read(get_themes_store_path()).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+/// Checks if the stores should be fetched again
+pub fn is_store_cache_expired(max_age: Duration) -> bool {
+    match get_extensions_store() {
+        Some(store) => store.is_expired(max_age),
+        None => true,
+    }
+}
+
+/// Finds the extensions whose id, name, description, author or tags contain the text. The search is case insensitive.
+pub fn search_store_extensions(
+    extensions: &[StoreExtension],
+    text: impl AsRef<str>,
+) -> Vec<&StoreExtension> {
+    let query = text.as_ref().trim().to_lowercase();
+
+    extensions
+        .iter()
+        .filter(|extension| extension.matches(&query))
+        .collect()
+}
+
+/// Finds the themes whose id, name, description, author or tags contain the text. The search is case insensitive.
+pub fn search_store_themes(themes: &[StoreTheme], text: impl AsRef<str>) -> Vec<&StoreTheme> {
+    let query = text.as_ref().trim().to_lowercase();
+
+    themes
+        .iter()
+        .filter(|theme| theme.matches(&query))
+        .collect()
+}
+
+/// The extensions that can be installed in the current os
+pub fn filter_store_extensions_by_os(extensions: &[StoreExtension]) -> Vec<&StoreExtension> {
+    extensions
+        .iter()
+        .filter(|extension| extension.os == "*" || extension.os == std::env::consts::OS)
+        .collect()
+}
+
+/// The store extensions that are newer than the installed ones
+pub fn get_store_extension_updates<'a>(
+    extensions: &'a [StoreExtension],
+    installed: &[ExtensionIndexEntry],
+) -> Vec<&'a StoreExtension> {
+    extensions
+        .iter()
+        .filter(|extension| installed.iter().any(|entry| extension.is_update_for(entry)))
+        .collect()
+}
+
+/// The store extensions that aren't installed
+pub fn get_store_extensions_not_installed<'a>(
+    extensions: &'a [StoreExtension],
+    installed: &[ExtensionIndexEntry],
+) -> Vec<&'a StoreExtension> {
+    extensions
+        .iter()
+        .filter(|extension| {
+            !installed
+                .iter()
+                .any(|entry| entry.manifest.id == extension.id)
+        })
+        .collect()
+}
+
+fn matches_query<'a>(query: &str, fields: impl IntoIterator<Item = &'a String>) -> bool {
+    query.is_empty()
+        || fields
+            .into_iter()
+            .any(|field| field.to_lowercase().contains(query))
+}
+
+fn verify_checksum(path: &Path, checksum: &Option<String>) -> Result<bool> {
+    let checksum = match checksum {
+        Some(checksum) => checksum,
+        None => return Ok(true),
+    };
+
+    let mut file = File::open(path).map_err(|error| Error::from_read(error, path))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    let hash = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    Ok(hash.eq_ignore_ascii_case(checksum.trim()))
+}
+
+fn get_unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn default_extensions() -> Vec<StoreExtension> {
+    vec![]
+}
+
+fn default_themes() -> Vec<StoreTheme> {
+    vec![]
+}
+
+fn default_string() -> String {
+    String::new()
+}
+
+fn default_optional_string() -> Option<String> {
+    None
+}
+
+fn default_os() -> String {
+    "*".to_string()
+}
+
+fn default_tags() -> Vec<String> {
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, process};
+
+    use super::*;
+
+    const CATALOG: &str = r#"{
+        "extensions": [
+            {
+                "id": "weather",
+                "name": "Weather",
+                "version": "1.2.0",
+                "download_url": "https://example.com/weather.tar.gz",
+                "checksum": "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+                "tags": ["forecast"]
+            }
+        ]
+    }"#;
+
+    /// A file in the temporary directory, unique to the test and the process
+    fn get_temp_path(name: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("whiskers-launcher-store-{}-{name}", process::id()));
+        path
+    }
+
+    #[test]
+    fn from_file_reads_a_local_catalog() {
+        let path = get_temp_path("catalog.json");
+        fs::write(&path, CATALOG).unwrap();
+
+        let catalog = StoreCatalog::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(catalog.extensions.len(), 1);
+        assert!(catalog.themes.is_empty());
+
+        let extension = &catalog.extensions[0];
+        assert_eq!(extension.id, "weather");
+        assert_eq!(extension.description, "");
+        assert_eq!(extension.os, "*");
+        assert_eq!(
+            search_store_extensions(&catalog.extensions, "FORECAST").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn from_file_reports_missing_and_invalid_catalogs() {
+        let path = get_temp_path("missing.json");
+        assert!(matches!(
+            StoreCatalog::from_file(&path),
+            Err(Error::MissingFile(_))
+        ));
+
+        let path = get_temp_path("invalid.json");
+        fs::write(&path, "{ \"extensions\": 1 }").unwrap();
+
+        let result = StoreCatalog::from_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::Json(_))));
+    }
+
+    #[test]
+    fn is_expired_compares_the_age_with_the_max_age() {
+        let mut cache = StoreCache::<StoreExtension>::new("catalog.json", vec![]);
+        assert!(!cache.is_expired(DEFAULT_STORE_EXPIRY));
+        assert!(cache.is_expired(Duration::ZERO));
+
+        cache.fetched_at -= DEFAULT_STORE_EXPIRY.as_secs();
+        assert!(cache.is_expired(DEFAULT_STORE_EXPIRY));
+
+        // A clock that went backwards doesn't expire the cache
+        cache.fetched_at = get_unix_time() + 60;
+        assert!(!cache.is_expired(DEFAULT_STORE_EXPIRY));
+    }
+
+    #[test]
+    fn verify_checksum_compares_the_sha256() {
+        let path = get_temp_path("download");
+        fs::write(&path, "hello").unwrap();
+
+        let checksum = "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824 ";
+        let matches = verify_checksum(&path, &Some(checksum.to_owned()));
+        let wrong = verify_checksum(&path, &Some("00".repeat(32)));
+        let missing = verify_checksum(&path, &None);
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches.unwrap());
+        assert!(!wrong.unwrap());
+        assert!(missing.unwrap());
+    }
+}
@@ -0,0 +1,297 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+#[cfg(target_os = "linux")]
+use freedesktop_desktop_entry::default_paths;
+
+use crate::{
+    error::Result,
+    features::extensions::IndexReport,
+    paths::{get_extensions_dir, get_settings_generation_path, get_settings_path},
+};
+
+use super::{
+    apps::index_apps,
+    extensions::index_extensions,
+    settings::{get_settings, get_settings_generation, try_get_settings, SettingsChange},
+};
+
+/// How long the watcher waits for more changes before indexing again
+pub const DEFAULT_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What the watcher indexed again
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// The extensions were indexed again and some of them changed
+    Extensions(IndexReport),
+
+    /// The apps were indexed again
+    Apps,
+}
+
+/// Indexes the extensions and apps again when they change. It stops watching when dropped.
+pub struct IndexWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+#[derive(Default)]
+struct PendingChanges {
+    extensions: bool,
+    apps: bool,
+}
+
+impl IndexWatcher {
+    /// Watches the extensions directory and, on linux, the applications directories.
+    ///
+    /// Changes are debounced and the callback is called from a background thread after indexing.
+    pub fn new(
+        debounce: Duration,
+        mut callback: impl FnMut(WatchEvent) + Send + 'static,
+    ) -> Result<Self> {
+        let extensions_dir = get_extensions_dir();
+        fs::create_dir_all(&extensions_dir)?;
+
+        let apps_dirs = get_apps_dirs();
+        let (sender, receiver) = mpsc::channel::<Event>();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = sender.send(event);
+            }
+        })
+        .map_err(std::io::Error::other)?;
+
+        watcher
+            .watch(&extensions_dir, RecursiveMode::NonRecursive)
+            .map_err(std::io::Error::other)?;
+
+        let mut watched_extension_dirs = Vec::new();
+        watch_extension_dirs(&mut watcher, &extensions_dir, &mut watched_extension_dirs);
+
+        for dir in &apps_dirs {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .map_err(std::io::Error::other)?;
+        }
+
+        let watcher = Arc::new(Mutex::new(watcher));
+
+        // Weak, so dropping the index watcher still drops the watcher and ends the thread
+        let thread_watcher = Arc::downgrade(&watcher);
+
+        thread::spawn(move || {
+            // Ends when the watcher is dropped, since it owns the sender
+            while let Ok(event) = receiver.recv() {
+                let mut pending = PendingChanges::default();
+                pending.add(&event, &extensions_dir, &apps_dirs);
+
+                loop {
+                    match receiver.recv_timeout(debounce) {
+                        Ok(event) => pending.add(&event, &extensions_dir, &apps_dirs),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                if pending.extensions {
+                    if let Some(watcher) = thread_watcher.upgrade() {
+                        watch_extension_dirs(
+                            &mut watcher.lock().unwrap(),
+                            &extensions_dir,
+                            &mut watched_extension_dirs,
+                        );
+                    }
+
+                    let report = index_extensions();
+
+                    if report.has_changes() {
+                        callback(WatchEvent::Extensions(report));
+                    }
+                }
+
+                if pending.apps {
+                    index_apps();
+                    callback(WatchEvent::Apps);
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Calls the callback when the settings change, like when they're written by another process. It stops watching when
+/// dropped.
+pub struct SettingsWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl SettingsWatcher {
+    /// Watches the settings file and its generation counter.
+    ///
+    /// Changes are debounced and the callback is called from a background thread. Writes that don't change any setting
+    /// and settings files that can't be read, like while they're edited by hand, are skipped.
+    pub fn new(
+        debounce: Duration,
+        mut callback: impl FnMut(SettingsChange) + Send + 'static,
+    ) -> Result<Self> {
+        let mut settings = get_settings();
+        let settings_path = get_settings_path();
+        let generation_path = get_settings_generation_path();
+        let (sender, receiver) = mpsc::channel::<Event>();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if let Ok(event) = event {
+                let _ = sender.send(event);
+            }
+        })
+        .map_err(std::io::Error::other)?;
+
+        // The files are replaced on every write, so the directory is watched instead
+        if let Some(settings_dir) = settings_path.parent() {
+            watcher
+                .watch(settings_dir, RecursiveMode::NonRecursive)
+                .map_err(std::io::Error::other)?;
+        }
+
+        let is_settings_change = move |event: &Event| {
+            !matches!(event.kind, EventKind::Access(_))
+                && event
+                    .paths
+                    .iter()
+                    .any(|path| path == &settings_path || path == &generation_path)
+        };
+
+        thread::spawn(move || {
+            // Ends when the watcher is dropped, since it owns the sender
+            while let Ok(event) = receiver.recv() {
+                let mut changed = is_settings_change(&event);
+
+                loop {
+                    match receiver.recv_timeout(debounce) {
+                        Ok(event) => changed |= is_settings_change(&event),
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+
+                if !changed {
+                    continue;
+                }
+
+                let Ok(new_settings) = try_get_settings() else {
+                    continue;
+                };
+
+                let change = SettingsChange::new(
+                    get_settings_generation(),
+                    settings,
+                    new_settings.to_owned(),
+                );
+
+                settings = new_settings;
+
+                if !change.changed.is_empty() {
+                    callback(change);
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+impl PendingChanges {
+    fn add(&mut self, event: &Event, extensions_dir: &Path, apps_dirs: &[PathBuf]) {
+        for path in &event.paths {
+            if is_extension_change(path, extensions_dir) {
+                self.extensions = true;
+            }
+
+            if is_app_change(path, apps_dirs) {
+                self.apps = true;
+            }
+        }
+    }
+}
+
+/// Watches every extension directory, but not their subdirectories, since only the manifests at their root are
+/// indexed. Build directories like `target` or `node_modules` would use up the watches and keep the debounce going.
+fn watch_extension_dirs(
+    watcher: &mut RecommendedWatcher,
+    extensions_dir: &Path,
+    watched_dirs: &mut Vec<PathBuf>,
+) {
+    let dirs = match fs::read_dir(extensions_dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+
+    watched_dirs.retain(|dir| {
+        let exists = dirs.contains(dir);
+
+        if !exists {
+            let _ = watcher.unwatch(dir);
+        }
+
+        exists
+    });
+
+    for dir in dirs {
+        if !watched_dirs.contains(&dir) && watcher.watch(&dir, RecursiveMode::NonRecursive).is_ok()
+        {
+            watched_dirs.push(dir);
+        }
+    }
+}
+
+/// Only extension directories and their manifests matter, not changes inside things like `target` or `node_modules`
+fn is_extension_change(path: &Path, extensions_dir: &Path) -> bool {
+    let relative_path = match path.strip_prefix(extensions_dir) {
+        Ok(relative_path) => relative_path,
+        Err(_) => return false,
+    };
+
+    match relative_path.components().count() {
+        1 => true,
+        2 => relative_path.ends_with("manifest.json"),
+        _ => false,
+    }
+}
+
+fn is_app_change(path: &Path, apps_dirs: &[PathBuf]) -> bool {
+    apps_dirs.iter().any(|dir| path.starts_with(dir))
+        && path
+            .extension()
+            .is_some_and(|extension| extension == "desktop")
+}
+
+/// The directories with the desktop entries of the installed apps
+fn get_apps_dirs() -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    {
+        default_paths()
+            .into_iter()
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
@@ -1,28 +1,63 @@
-#[cfg(target_os = "windows")]
-use {
-    crate::paths::get_extension_response_path,
-    std::io::Write,
-    std::fs::File
-};
-
 use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env, fmt,
     fs::{self},
+    panic::{self, AssertUnwindSafe},
     path::PathBuf,
-    process::exit,
+    process::{self, exit, Command},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+#[cfg(unix)]
+use {
+    crate::{
+        paths::get_extension_daemon_socket_path, protocol::decode_json, transport::SocketTransport,
+    },
+    std::os::unix::net::UnixListener,
+};
 
 use crate::{
-    paths::get_extensions_dir,
-    results::SearchResults,
+    error::{Error, Result},
+    features::core::extensions::{
+        get_manifest_paths, try_get_extension_index, try_get_extension_request,
+    },
+    paths::{get_cancelled_requests_dir, get_extension_response_path},
+    protocol::{encode_json, MessageEncoding, SearchResultsV1},
+    results::{ExtensionError, SearchResult, SearchResults, SearchResultsMessage, SearchViewType},
+    transport::{connect_transport, FileTransport, Transport, TransportKind, TRANSPORT_ENV},
+    utils::{is_newer_version, parse_version, CORE_VERSION},
+};
+
+use super::core::{
+    secrets::get_secret,
+    settings::{
+        get_settings, is_secret_setting, parse_bool, set_extension_setting, ExtensionSetting,
+    },
 };
 
-use super::core::settings::get_settings;
+/// Environment variable the launcher uses to tell an extension which request it is answering
+pub const REQUEST_ID_ENV: &str = "WHISKERS_LAUNCHER_REQUEST_ID";
+
+/// Environment variable with the extension id, set by the launcher when it starts an extension as a daemon
+pub const DAEMON_ENV: &str = "WHISKERS_LAUNCHER_DAEMON";
+
+thread_local! {
+    /// The request a daemon is serving. Daemons get their requests through a socket instead of the environment.
+    static DAEMON_REQUEST: RefCell<Option<ExtensionRequest>> = const { RefCell::new(None) };
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ExtensionRequest {
+    /// A unique id used to match the request with its response
+    pub request_id: String,
     pub extension_id: String,
     pub request_type: ExtensionRequestType,
     pub search_text: Option<String>,
@@ -30,26 +65,52 @@ pub struct ExtensionRequest {
     pub args: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ExtensionRequestType {
     GetResults,
     RunCommand,
+    /// Asks an extension running as a daemon to stop
+    Shutdown,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormResponse {
     pub results: Vec<FormResult>,
     pub args: Vec<String>,
+    /// The id of the request that carries this response to the extension
+    pub request_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormResult {
     pub field_id: String,
     pub field_value: String,
     pub args: Vec<String>,
 }
 
+/// Sends search results to the launcher in batches, so the first results show up before the search ends.
+///
+/// Launchers that don't support streaming get every batch at once when the sink finishes.
+pub struct ResultSink {
+    transport: Option<Box<dyn Transport>>,
+    view_type: SearchViewType,
+    request_id: Option<String>,
+    pending: Vec<SearchResult>,
+    finished: bool,
+}
+
+/// The settings of the extension answering a request. Other extensions' settings can't be read through it.
+#[derive(Debug, Clone)]
+pub struct ExtensionContext {
+    request: ExtensionRequest,
+    settings: Vec<ExtensionSetting>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ExtensionManifest {
     pub id: String,
     pub name: String,
@@ -59,9 +120,91 @@ pub struct ExtensionManifest {
     pub settings: Option<Vec<ExtensionManifestSetting>>,
     #[serde(default = "default_os")]
     pub os: String,
+    /// The transports the extension can answer with. When not set, protocol 1 extensions use the legacy one and newer ones any.
+    #[serde(default = "default_transports")]
+    pub transports: Option<Vec<TransportKind>>,
+    /// How many milliseconds the launcher waits for the extension before killing it
+    #[serde(default = "default_timeout")]
+    pub timeout: Option<u64>,
+    #[serde(default = "default_mode")]
+    pub mode: ExtensionMode,
+    /// The protocol version the extension was built with
+    #[serde(default = "default_protocol")]
+    pub protocol: u32,
+    /// The oldest protocol version the extension can work with
+    #[serde(default = "default_protocol")]
+    pub min_protocol: u32,
+    /// How the extension wants to read the requests and form responses. Extensions not written in rust should use json.
+    #[serde(default = "default_encoding")]
+    pub encoding: MessageEncoding,
+    /// The file that runs the extension, relative to the extension directory
+    #[serde(default = "default_exec")]
+    pub exec: Option<String>,
+    /// The program used to run [`ExtensionManifest::exec`], like `python3` or `deno`. When not set, the file is run directly.
+    #[serde(default = "default_runtime")]
+    pub runtime: Option<String>,
+    /// The arguments given to the runtime before the file
+    #[serde(default = "default_args")]
+    pub runtime_args: Vec<String>,
+    /// The arguments given to the extension after the file
+    #[serde(default = "default_args")]
+    pub args: Vec<String>,
+    /// The directory the extension runs in, relative to the extension directory. Defaults to the extension directory.
+    #[serde(default = "default_cwd")]
+    pub cwd: Option<String>,
+    /// Environment variables set when running the extension
+    #[serde(default = "default_env")]
+    pub env: HashMap<String, String>,
+    /// The semantic version of the extension, like `1.2.0`
+    #[serde(default = "default_metadata")]
+    pub version: Option<String>,
+    #[serde(default = "default_metadata")]
+    pub author: Option<String>,
+    #[serde(default = "default_metadata")]
+    pub homepage: Option<String>,
+    /// The extension icon, relative to the extension directory
+    #[serde(default = "default_metadata")]
+    pub icon: Option<String>,
+    #[serde(default = "default_metadata")]
+    pub license: Option<String>,
+    /// The oldest version of this crate the extension works with
+    #[serde(default = "default_metadata")]
+    pub min_core_version: Option<String>,
 }
 
+/// An indexed extension
 #[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionIndexEntry {
+    pub manifest: ExtensionManifest,
+    /// The directory the manifest is in
+    pub dir: PathBuf,
+    pub manifest_path: PathBuf,
+    /// When the manifest was last modified. Unchanged manifests aren't read again when indexing.
+    pub modified: Option<SystemTime>,
+}
+
+/// The extensions that changed since the previous indexing, by id
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct IndexReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum ExtensionMode {
+    /// The extension is launched for every request and exits after answering it
+    OneShot,
+
+    /// The extension keeps running and answers requests through a socket until it's shut down.
+    ///
+    /// It must use [`run_extension`] to serve the requests.
+    Daemon,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ExtensionManifestSetting {
     pub id: String,
     pub title: String,
@@ -74,34 +217,118 @@ pub struct ExtensionManifestSetting {
     pub select_options: Option<Vec<ExtensionManifestSelectOption>>,
     #[serde(default = "default_os")]
     pub os: String,
+    /// The smallest value of number and slider settings
+    #[serde(default = "default_limit")]
+    pub min: Option<f64>,
+    /// The biggest value of number and slider settings
+    #[serde(default = "default_limit")]
+    pub max: Option<f64>,
+    /// The increment of slider settings
+    #[serde(default = "default_limit")]
+    pub step: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ExtensionManifestSettingType {
     Input,
     TextArea,
     Select,
     Toggle,
+    /// A number between [`ExtensionManifestSetting::min`] and [`ExtensionManifestSetting::max`]
+    Number,
+    /// A number between [`ExtensionManifestSetting::min`] and [`ExtensionManifestSetting::max`], picked with a slider
+    Slider,
+    /// A hex color, like `#FFE072`
+    Color,
+    FilePicker,
+    FolderPicker,
+    /// A key combination, like `ctrl+shift+k`
+    Hotkey,
+    /// Stored encrypted outside the settings, see [`crate::features::core::secrets`]
+    Password,
+    /// Same as [`ExtensionManifestSettingType::Password`]
+    Secret,
+}
+
+impl ExtensionManifestSettingType {
+    /// Checks if the values of this type are stored as secrets instead of in the settings
+    pub fn is_secret(&self) -> bool {
+        matches!(
+            self,
+            ExtensionManifestSettingType::Password | ExtensionManifestSettingType::Secret
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ExtensionManifestShowCondition {
+    /// The setting compared. Conditions that are only a group or an os predicate don't need it.
+    #[serde(default = "default_condition_string")]
     pub setting_id: String,
+    #[serde(default = "default_condition_string")]
     pub setting_value: String,
+    #[serde(default = "default_condition_operator")]
+    pub operator: ShowConditionOperator,
+    /// The values compared by [`ShowConditionOperator::OneOf`]
+    #[serde(default = "default_condition_values")]
+    pub setting_values: Vec<String>,
+    /// Met when all the conditions are met
+    #[serde(default = "default_condition_group")]
+    pub all: Option<Vec<ExtensionManifestShowCondition>>,
+    /// Met when any of the conditions is met
+    #[serde(default = "default_condition_group")]
+    pub any: Option<Vec<ExtensionManifestShowCondition>>,
+    /// Met when running in the os, like `linux` or `windows`
+    #[serde(default = "default_condition_os")]
+    pub os: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum ShowConditionOperator {
+    Equal,
+    NotEqual,
+    OneOf,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ExtensionManifestSelectOption {
     pub id: String,
     pub text: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    /// The extension can't be loaded
+    Error,
+
+    /// The extension is loaded but part of it may not work as expected
+    Warning,
+}
+
+/// A problem found in an extension manifest
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManifestDiagnostic {
+    pub path: PathBuf,
+    /// Json pointer to the value with the problem, like `/settings/0/default_value`. Empty when it's about the whole manifest.
+    pub pointer: String,
+    /// Only known when the manifest isn't valid json or doesn't match the manifest layout
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
 impl ExtensionRequest {
     pub fn new_get_results_request(
         extension_id: impl Into<String>,
         search_text: impl Into<String>,
     ) -> Self {
         Self {
+            request_id: new_request_id(),
             extension_id: extension_id.into(),
             request_type: ExtensionRequestType::GetResults,
             search_text: Some(search_text.into()),
@@ -110,11 +337,23 @@ impl ExtensionRequest {
         }
     }
 
+    pub fn new_shutdown_request(extension_id: impl Into<String>) -> Self {
+        Self {
+            request_id: new_request_id(),
+            extension_id: extension_id.into(),
+            request_type: ExtensionRequestType::Shutdown,
+            search_text: None,
+            command: None,
+            args: vec![],
+        }
+    }
+
     pub fn new_run_command_request(
         extension_id: impl Into<String>,
         command: impl Into<String>,
     ) -> Self {
         Self {
+            request_id: new_request_id(),
             extension_id: extension_id.into(),
             request_type: ExtensionRequestType::RunCommand,
             search_text: None,
@@ -134,6 +373,389 @@ impl ExtensionRequest {
         self.args = args;
         self
     }
+
+    /// Tells the extension spawned by the command which request it is answering
+    pub fn set_env(&self, command: &mut Command) {
+        command.env(REQUEST_ID_ENV, &self.request_id);
+    }
+}
+
+impl ExtensionContext {
+    /// The context of the request the extension was launched for
+    pub fn current() -> Result<Self> {
+        Ok(Self::from_request(try_get_extension_request()?))
+    }
+
+    /// The settings are only read from the disk once per process. Use [`ExtensionContext::reload`] to read them again.
+    pub fn from_request(request: ExtensionRequest) -> Self {
+        let settings = get_cached_extension_settings(&request.extension_id);
+
+        Self { request, settings }
+    }
+
+    pub fn get_request(&self) -> &ExtensionRequest {
+        &self.request
+    }
+
+    pub fn get_extension_id(&self) -> &str {
+        &self.request.extension_id
+    }
+
+    pub fn get_extension_dir(&self) -> Option<PathBuf> {
+        get_extension_dir(&self.request.extension_id)
+    }
+
+    pub fn get_settings(&self) -> &[ExtensionSetting] {
+        &self.settings
+    }
+
+    pub fn get_setting(&self, setting_id: impl AsRef<str>) -> Option<&ExtensionSetting> {
+        let setting_id = setting_id.as_ref();
+        self.settings.iter().find(|s| s.setting_id == setting_id)
+    }
+
+    pub fn get_value(&self, setting_id: impl AsRef<str>) -> Option<String> {
+        self.get_setting(setting_id)
+            .map(|setting| setting.setting_value.to_owned())
+    }
+
+    pub fn get_bool(&self, setting_id: impl AsRef<str>) -> Option<bool> {
+        self.get_setting(setting_id)?.get_bool()
+    }
+
+    pub fn get_i64(&self, setting_id: impl AsRef<str>) -> Option<i64> {
+        self.get_setting(setting_id)?.get_i64()
+    }
+
+    pub fn get_f64(&self, setting_id: impl AsRef<str>) -> Option<f64> {
+        self.get_setting(setting_id)?.get_f64()
+    }
+
+    pub fn get_enum<T: DeserializeOwned>(&self, setting_id: impl AsRef<str>) -> Option<T> {
+        self.get_setting(setting_id)?.get_enum()
+    }
+
+    pub fn get_list(&self, setting_id: impl AsRef<str>) -> Vec<String> {
+        self.get_setting(setting_id)
+            .map(|setting| setting.get_list())
+            .unwrap_or_default()
+    }
+
+    /// Gets the value of one of the extension password or secret settings
+    pub fn get_secret(&self, setting_id: impl Into<String>) -> Option<String> {
+        get_secret(&self.request.extension_id, setting_id)
+            .ok()
+            .flatten()
+    }
+
+    /// Validates the value against the extension manifest and saves it. Secret settings are saved with the secrets.
+    pub fn set_value(
+        &mut self,
+        setting_id: impl Into<String>,
+        setting_value: impl Into<String>,
+    ) -> Result<()> {
+        let setting_id = setting_id.into();
+        let extension_id = self.request.extension_id.to_owned();
+
+        let setting_value = set_extension_setting(&extension_id, &setting_id, setting_value)?;
+
+        if is_secret_setting(&extension_id, &setting_id) {
+            return Ok(());
+        }
+
+        let setting = ExtensionSetting {
+            extension_id,
+            setting_id,
+            setting_value,
+        };
+
+        update_cached_extension_setting(&setting);
+
+        match self
+            .settings
+            .iter_mut()
+            .find(|s| s.setting_id == setting.setting_id)
+        {
+            Some(current_setting) => *current_setting = setting,
+            None => self.settings.push(setting),
+        }
+
+        Ok(())
+    }
+
+    /// Reads the settings from the disk again, like after the user changed them while a daemon was running
+    pub fn reload(&mut self) {
+        *get_settings_cache().lock().unwrap() = None;
+        self.settings = get_cached_extension_settings(&self.request.extension_id);
+    }
+}
+
+impl ExtensionManifest {
+    /// Tells the extension spawned by the command to run as a daemon
+    pub fn set_daemon_env(&self, command: &mut Command) {
+        command.env(DAEMON_ENV, &self.id);
+    }
+
+    /// Checks if the extension works with this version of the crate. Extensions without a minimum version are always supported.
+    pub fn is_supported_by_core(&self) -> bool {
+        match &self.min_core_version {
+            Some(min_core_version) => !is_newer_version(min_core_version, CORE_VERSION),
+            None => true,
+        }
+    }
+
+    /// Checks if the version is newer than the extension version. Always true when the extension has no valid version.
+    pub fn is_outdated(&self, latest_version: impl AsRef<str>) -> bool {
+        match &self.version {
+            Some(version) if parse_version(version).is_some() => {
+                is_newer_version(latest_version, version)
+            }
+            _ => parse_version(latest_version).is_some(),
+        }
+    }
+
+    /// The transports the extension can answer with.
+    ///
+    /// Extensions that don't declare them can use every supported transport, so linux uses a unix socket. Protocol 1
+    /// extensions were built before transports existed, so they only answer with the legacy one.
+    pub fn get_transports(&self) -> Vec<TransportKind> {
+        match &self.transports {
+            Some(transports) => transports.to_owned(),
+            None if self.protocol < 2 => vec![TransportKind::legacy()],
+            None => TransportKind::supported(),
+        }
+    }
+
+    /// The file that runs the extension, relative to the extension directory.
+    ///
+    /// Extensions that don't declare it are run from the binary the launcher used before entry points were declared.
+    ///
+    /// **linux** => extension
+    ///
+    /// **windows** => extension.exe
+    pub fn get_exec(&self) -> String {
+        match &self.exec {
+            Some(exec) => exec.to_owned(),
+            None if cfg!(target_os = "windows") => String::from("extension.exe"),
+            None => String::from("extension"),
+        }
+    }
+}
+
+impl ExtensionManifestSetting {
+    /// Checks if the value can be stored in the setting. Returns the value in the form it should be stored in, like
+    /// `true` for a toggle set to `yes`.
+    pub fn validate_value(&self, value: impl AsRef<str>) -> Result<String> {
+        let value = value.as_ref();
+
+        let invalid = |message: String| Error::InvalidSetting {
+            setting_id: self.id.to_owned(),
+            message,
+        };
+
+        match self.setting_type {
+            ExtensionManifestSettingType::Toggle => parse_bool(value)
+                .map(|value| value.to_string())
+                .ok_or_else(|| invalid(format!("`{value}` is not true or false"))),
+            ExtensionManifestSettingType::Select => {
+                let options = self.select_options.as_deref().unwrap_or_default();
+
+                if options.iter().any(|option| option.id == value) {
+                    Ok(value.to_owned())
+                } else {
+                    Err(invalid(format!(
+                        "`{value}` is not one of the select options"
+                    )))
+                }
+            }
+            ExtensionManifestSettingType::Number | ExtensionManifestSettingType::Slider => {
+                let number = value
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .ok_or_else(|| invalid(format!("`{value}` is not a number")))?;
+
+                if self.min.is_some_and(|min| number < min) {
+                    return Err(invalid(format!(
+                        "{number} is smaller than {}",
+                        self.min.unwrap()
+                    )));
+                }
+
+                if self.max.is_some_and(|max| number > max) {
+                    return Err(invalid(format!(
+                        "{number} is bigger than {}",
+                        self.max.unwrap()
+                    )));
+                }
+
+                Ok(number.to_string())
+            }
+            ExtensionManifestSettingType::Color => {
+                let hex = value.trim().strip_prefix('#').unwrap_or_default();
+                let valid = [3, 4, 6, 8].contains(&hex.len())
+                    && hex.chars().all(|char| char.is_ascii_hexdigit());
+
+                if valid {
+                    Ok(value.trim().to_uppercase())
+                } else {
+                    Err(invalid(format!(
+                        "`{value}` is not a hex color, like #FFE072"
+                    )))
+                }
+            }
+            ExtensionManifestSettingType::Hotkey => {
+                let keys = value.split('+').map(str::trim).collect::<Vec<_>>();
+
+                if keys.iter().any(|key| key.is_empty()) {
+                    Err(invalid(format!(
+                        "`{value}` is not a key combination, like ctrl+shift+k"
+                    )))
+                } else {
+                    Ok(keys.join("+"))
+                }
+            }
+            ExtensionManifestSettingType::Input
+            | ExtensionManifestSettingType::TextArea
+            | ExtensionManifestSettingType::FilePicker
+            | ExtensionManifestSettingType::FolderPicker
+            | ExtensionManifestSettingType::Password
+            | ExtensionManifestSettingType::Secret => Ok(value.to_owned()),
+        }
+    }
+}
+
+impl ExtensionManifestShowCondition {
+    /// Checks the condition. Every part that is set must be met.
+    ///
+    /// The value function gets the current value of a setting.
+    pub fn is_met(&self, get_value: &impl Fn(&str) -> Option<String>) -> bool {
+        if let Some(os) = &self.os {
+            if !is_current_os(os) {
+                return false;
+            }
+        }
+
+        if let Some(all) = &self.all {
+            if !all.iter().all(|condition| condition.is_met(get_value)) {
+                return false;
+            }
+        }
+
+        if let Some(any) = &self.any {
+            if !any.iter().any(|condition| condition.is_met(get_value)) {
+                return false;
+            }
+        }
+
+        if self.setting_id.is_empty() {
+            return true;
+        }
+
+        let value = get_value(&self.setting_id).unwrap_or_default();
+
+        match self.operator {
+            ShowConditionOperator::Equal => value == self.setting_value,
+            ShowConditionOperator::NotEqual => value != self.setting_value,
+            ShowConditionOperator::OneOf => self.setting_values.contains(&value),
+        }
+    }
+}
+
+impl ExtensionIndexEntry {
+    /// The absolute path of the extension icon
+    pub fn get_icon_path(&self) -> Option<PathBuf> {
+        let icon = self.manifest.icon.as_ref()?;
+        let mut path = self.dir.to_owned();
+        path.push(icon);
+
+        Some(path)
+    }
+}
+
+impl IndexReport {
+    pub fn new(previous: &[ExtensionIndexEntry], current: &[ExtensionIndexEntry]) -> Self {
+        let mut report = Self::default();
+
+        for entry in current {
+            match previous.iter().find(|p| p.manifest.id == entry.manifest.id) {
+                Some(previous_entry) => {
+                    if previous_entry.manifest_path != entry.manifest_path
+                        || previous_entry.modified != entry.modified
+                    {
+                        report.changed.push(entry.manifest.id.to_owned());
+                    }
+                }
+                None => report.added.push(entry.manifest.id.to_owned()),
+            }
+        }
+
+        for entry in previous {
+            if !current.iter().any(|c| c.manifest.id == entry.manifest.id) {
+                report.removed.push(entry.manifest.id.to_owned());
+            }
+        }
+
+        report
+    }
+
+    /// Checks if any extension was added, removed or changed
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty() || !self.changed.is_empty()
+    }
+}
+
+impl ManifestDiagnostic {
+    pub fn error(
+        path: impl Into<PathBuf>,
+        pointer: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            pointer: pointer.into(),
+            line: None,
+            column: None,
+            severity: DiagnosticSeverity::Error,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(
+        path: impl Into<PathBuf>,
+        pointer: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity: DiagnosticSeverity::Warning,
+            ..Self::error(path, pointer, message)
+        }
+    }
+
+    pub fn from_json_error(path: impl Into<PathBuf>, error: &serde_json::Error) -> Self {
+        Self {
+            line: Some(error.line()),
+            column: Some(error.column()),
+            ..Self::error(path, "", error.to_string())
+        }
+    }
+}
+
+impl fmt::Display for ManifestDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, ":{line}:{column}")?;
+        }
+
+        if !self.pointer.is_empty() {
+            write!(f, " ({})", self.pointer)?;
+        }
+
+        write!(f, ": {:?}: {}", self.severity, self.message)
+    }
 }
 
 impl FormResponse {
@@ -141,9 +763,15 @@ impl FormResponse {
         Self {
             results,
             args: vec![],
+            request_id: None,
         }
     }
 
+    pub fn set_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
     pub fn add_arg(mut self, arg: impl Into<String>) -> Self {
         let mut new_args = self.args;
         new_args.push(arg.into());
@@ -190,10 +818,185 @@ impl FormResult {
     }
 
     pub fn as_bool(&self) -> bool {
-        return self.field_value == "true";
+        self.field_value == "true"
     }
 }
 
+impl ResultSink {
+    pub fn new_list_sink() -> Self {
+        Self::new(SearchViewType::List)
+    }
+
+    pub fn new_grid_sink() -> Self {
+        Self::new(SearchViewType::Grid)
+    }
+
+    fn new(view_type: SearchViewType) -> Self {
+        let mut finished = false;
+
+        let transport = match env::var(TRANSPORT_ENV) {
+            Ok(_) => match connect_transport(TransportKind::from_env()) {
+                Ok(transport) => Some(transport),
+                // The launcher may have stopped listening, so there is nobody to send the results to
+                Err(error) => {
+                    eprintln!("Error connecting to the launcher: {error}");
+                    finished = true;
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        Self {
+            transport,
+            view_type,
+            request_id: get_current_request_id(),
+            pending: vec![],
+            finished,
+        }
+    }
+
+    fn from_transport(transport: Box<dyn Transport>, request_id: impl Into<String>) -> Self {
+        Self {
+            transport: Some(transport),
+            view_type: SearchViewType::List,
+            request_id: Some(request_id.into()),
+            pending: vec![],
+            finished: false,
+        }
+    }
+
+    /// Changes how the launcher shows the results. Must be called before pushing any result.
+    pub fn set_view_type(&mut self, view_type: SearchViewType) {
+        self.view_type = view_type;
+    }
+
+    /// Sends the results to the launcher as a new batch
+    pub fn push(&mut self, results: Vec<SearchResult>) {
+        if self.finished {
+            return;
+        }
+
+        match self.transport {
+            Some(_) => {
+                let mut batch = SearchResults::new_list_results(results)
+                    .set_view_type(self.view_type.to_owned());
+
+                batch.request_id = self.request_id.to_owned();
+
+                self.send_message(&SearchResultsMessage::Batch(batch));
+            }
+            None => self.pending.extend(results),
+        }
+    }
+
+    /// Tells the launcher there are no more results. Called automatically when the sink is dropped.
+    pub fn finish(mut self) {
+        self.send_done();
+    }
+
+    /// Tells the launcher the extension failed. No more results can be sent after it.
+    pub fn send_error(mut self, mut error: ExtensionError) {
+        if self.finished {
+            return;
+        }
+
+        if error.request_id.is_none() {
+            error.request_id = self.request_id.to_owned();
+        }
+
+        match self.transport {
+            Some(_) => self.send_message(&SearchResultsMessage::Error(error)),
+            None => match &error.details {
+                Some(details) => eprintln!("{}: {details}", error.message),
+                None => eprintln!("{}", error.message),
+            },
+        }
+
+        self.finished = true;
+    }
+
+    fn send_done(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        match self.transport {
+            Some(_) => self.send_message(&SearchResultsMessage::Done {
+                request_id: self.request_id.to_owned(),
+            }),
+            // Older launchers expect a single protocol 1 message
+            None => {
+                let results = SearchResultsV1::from(
+                    SearchResults::new_list_results(self.pending.to_owned())
+                        .set_view_type(self.view_type.to_owned()),
+                );
+
+                let transport_kind = TransportKind::from_env();
+
+                let message = match transport_kind {
+                    TransportKind::File => bincode::serialize(&results).map_err(Error::from),
+                    _ => serde_json::to_vec(&results).map_err(Error::from),
+                };
+
+                let result = message.and_then(|message| {
+                    let mut transport = match transport_kind {
+                        // Older launchers read the shared response file
+                        TransportKind::File => {
+                            Box::new(FileTransport::from_path(get_extension_response_path()))
+                        }
+                        _ => connect_transport(transport_kind)?,
+                    };
+
+                    Ok(transport.send(&message)?)
+                });
+
+                if let Err(error) = result {
+                    eprintln!("Error sending search results: {error}");
+                }
+            }
+        }
+
+        self.finished = true;
+    }
+
+    /// Sends a message through the transport.
+    ///
+    /// Errors are only logged, since the launcher may have stopped listening after a timeout and a daemon must keep
+    /// serving the next requests. Nothing else is sent after an error.
+    fn send_message(&mut self, message: &SearchResultsMessage) {
+        let Some(transport) = self.transport.as_mut() else {
+            return;
+        };
+
+        let result = encode_json(message).and_then(|message| Ok(transport.send(&message)?));
+
+        if let Err(error) = result {
+            eprintln!("Error sending message to the launcher: {error}");
+            self.finished = true;
+        }
+    }
+}
+
+impl Drop for ResultSink {
+    fn drop(&mut self) {
+        self.send_done();
+    }
+}
+
+pub(crate) fn new_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    format!("{nanos:x}-{:x}-{count:x}", process::id())
+}
+
 fn default_settings() -> Option<Vec<ExtensionManifestSetting>> {
     None
 }
@@ -202,6 +1005,75 @@ fn default_os() -> String {
     "*".to_string()
 }
 
+fn default_transports() -> Option<Vec<TransportKind>> {
+    None
+}
+
+fn default_timeout() -> Option<u64> {
+    None
+}
+
+fn default_mode() -> ExtensionMode {
+    ExtensionMode::OneShot
+}
+
+/// Manifests without a protocol are from extensions built before the protocol was versioned
+fn default_protocol() -> u32 {
+    1
+}
+
+fn default_encoding() -> MessageEncoding {
+    MessageEncoding::Binary
+}
+
+fn default_exec() -> Option<String> {
+    None
+}
+
+fn default_runtime() -> Option<String> {
+    None
+}
+
+fn default_args() -> Vec<String> {
+    vec![]
+}
+
+fn default_cwd() -> Option<String> {
+    None
+}
+
+fn default_env() -> HashMap<String, String> {
+    HashMap::new()
+}
+
+fn default_metadata() -> Option<String> {
+    None
+}
+
+fn default_limit() -> Option<f64> {
+    None
+}
+
+fn default_condition_string() -> String {
+    String::new()
+}
+
+fn default_condition_operator() -> ShowConditionOperator {
+    ShowConditionOperator::Equal
+}
+
+fn default_condition_values() -> Vec<String> {
+    vec![]
+}
+
+fn default_condition_group() -> Option<Vec<ExtensionManifestShowCondition>> {
+    None
+}
+
+fn default_condition_os() -> Option<String> {
+    None
+}
+
 fn default_show_conditions() -> Option<Vec<ExtensionManifestShowCondition>> {
     None
 }
@@ -210,56 +1082,293 @@ fn default_select_options() -> Option<Vec<ExtensionManifestSelectOption>> {
     None
 }
 
-/// **linux** => Prints a json of the search results so the launcher can directly read the results.
+/// Sends the search results to the launcher using the transport it requested and exits.
 ///
-/// **windows** => Writes the response in a binary file
+/// Use a [`ResultSink`] to send the results in batches instead.
+///
+/// Extensions launched by older launchers answer with the legacy transport, stdout on linux and the response file on windows.
 pub fn send_search_results(results: SearchResults) {
-    #[cfg(target_os = "linux")]
-    {
-        let results_json =
-            serde_json::to_string(&results).expect("Error serializing search results");
-        println!("{results_json}");
+    let mut sink = ResultSink::new(results.view_type);
+
+    if results.request_id.is_some() {
+        sink.request_id = results.request_id;
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        let mut file = File::create(get_extension_response_path()).unwrap();
-        
-        file.write_all(&bincode::serialize(&results).unwrap())
-            .unwrap();
+    sink.push(results.results);
+    sink.finish();
+
+    exit(0);
+}
+
+/// Sends an error to the launcher instead of results and exits.
+///
+/// The launcher shows it as an error row with the message and details.
+pub fn send_extension_error(error: ExtensionError) {
+    ResultSink::new_list_sink().send_error(error);
+    exit(1);
+}
+
+/// Serves the launcher requests with the handler.
+///
+/// When the launcher starts the extension as a daemon it keeps serving requests through a socket until a
+/// [`ExtensionRequestType::Shutdown`] request arrives, so caches can be kept between requests. Otherwise it
+/// answers the request the extension was launched for and exits.
+pub fn run_extension(mut handler: impl FnMut(ExtensionRequest, &mut ResultSink)) {
+    #[cfg(unix)]
+    if let Ok(extension_id) = env::var(DAEMON_ENV) {
+        if let Err(error) = serve_daemon_requests(extension_id, &mut handler) {
+            eprintln!("Error serving daemon requests: {error}");
+            exit(1);
+        }
 
-        file.flush().unwrap();
-        file.sync_all().unwrap();
+        exit(0);
     }
 
+    let request = match try_get_extension_request() {
+        Ok(request) => request,
+        Err(error) => {
+            send_extension_error(
+                ExtensionError::new("Error getting extension request")
+                    .set_details(error.to_string()),
+            );
+            return;
+        }
+    };
+
+    handle_request(&mut handler, request, ResultSink::new_list_sink());
     exit(0);
 }
 
+/// Runs the handler, reporting a panic to the launcher as an [`ExtensionError`] instead of silently dying
+fn handle_request(
+    handler: &mut impl FnMut(ExtensionRequest, &mut ResultSink),
+    request: ExtensionRequest,
+    mut sink: ResultSink,
+) {
+    match panic::catch_unwind(AssertUnwindSafe(|| handler(request, &mut sink))) {
+        Ok(_) => sink.finish(),
+        Err(payload) => {
+            let mut error = ExtensionError::new("The extension crashed");
+
+            if let Some(message) = payload.downcast_ref::<&str>() {
+                error = error.set_details(*message);
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                error = error.set_details(message);
+            }
+
+            sink.send_error(error);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn serve_daemon_requests(
+    extension_id: String,
+    handler: &mut impl FnMut(ExtensionRequest, &mut ResultSink),
+) -> Result<()> {
+    let socket_path = get_extension_daemon_socket_path(extension_id);
+
+    if let Some(parent) = socket_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if socket_path.exists() {
+        fs::remove_file(&socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&socket_path)?;
+
+    for stream in listener.incoming().flatten() {
+        let mut transport = SocketTransport::from_stream(stream);
+
+        let request = match transport.receive() {
+            Ok(Some(message)) => match decode_json::<ExtensionRequest>(&message) {
+                Ok(request) => request,
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+
+        if request.request_type == ExtensionRequestType::Shutdown {
+            break;
+        }
+
+        let request_id = request.request_id.to_owned();
+        let sink = ResultSink::from_transport(Box::new(transport), request_id);
+
+        DAEMON_REQUEST.with(|current| *current.borrow_mut() = Some(request.to_owned()));
+        handle_request(handler, request, sink);
+        DAEMON_REQUEST.with(|current| *current.borrow_mut() = None);
+    }
+
+    let _ = fs::remove_file(&socket_path);
+
+    Ok(())
+}
+
+/// The id of the request the extension is answering, when launched by a launcher that sets it or while a daemon
+/// serves a request
+pub fn get_current_request_id() -> Option<String> {
+    let daemon_request_id = DAEMON_REQUEST.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|request| request.request_id.to_owned())
+    });
+
+    daemon_request_id.or_else(|| env::var(REQUEST_ID_ENV).ok().filter(|id| !id.is_empty()))
+}
+
+/// The request a daemon is serving, see [`run_extension`]
+pub(crate) fn get_daemon_request() -> Option<ExtensionRequest> {
+    DAEMON_REQUEST.with(|current| current.borrow().to_owned())
+}
+
+/// Checks if the launcher cancelled the request the extension is answering, because the user kept typing for example.
+///
+/// Slow extensions should check it from time to time and stop working when it returns true.
+pub fn is_cancelled() -> bool {
+    match get_current_request_id() {
+        Some(request_id) => is_request_cancelled(request_id),
+        None => false,
+    }
+}
+
+pub fn is_request_cancelled(request_id: impl Into<String>) -> bool {
+    let mut path = get_cancelled_requests_dir();
+    path.push(request_id.into());
+    path.exists()
+}
+
+/// Gets the directory the extension is installed in, using the extensions index.
+///
+/// The extensions directory is only scanned when the index doesn't have the extension, like before the first indexing.
 pub fn get_extension_dir(extension_id: impl Into<String>) -> Option<PathBuf> {
     let extension_id = extension_id.into();
-    let extensions_dir = get_extensions_dir();
 
-    for entry in WalkDir::new(&extensions_dir) {
-        if entry.is_ok() {
-            let entry = entry.unwrap();
-            let name = entry.file_name();
+    if let Ok(entries) = try_get_extension_index() {
+        let dir = entries
+            .into_iter()
+            .find(|entry| entry.manifest.id == extension_id)
+            .map(|entry| entry.dir);
 
-            if name == "manifest.json" {
-                let json =
-                    fs::read_to_string(entry.path()).expect("Error getting manifest content");
-
-                if let Ok(extension) = serde_json::from_str::<ExtensionManifest>(&json) {
-                    if extension_id == extension.id {
-                        return Some(entry.path().parent().unwrap().to_owned());
-                    }
-                }
+        if let Some(dir) = dir {
+            if dir.exists() {
+                return Some(dir);
             }
         }
     }
 
-    return None;
+    get_manifest_paths().into_iter().find_map(|path| {
+        let json = fs::read_to_string(&path).ok()?;
+        let extension = serde_json::from_str::<ExtensionManifest>(&json).ok()?;
+
+        if extension.id == extension_id {
+            Some(path.parent()?.to_owned())
+        } else {
+            None
+        }
+    })
+}
+
+fn get_settings_cache() -> &'static Mutex<Option<Vec<ExtensionSetting>>> {
+    static SETTINGS_CACHE: OnceLock<Mutex<Option<Vec<ExtensionSetting>>>> = OnceLock::new();
+    SETTINGS_CACHE.get_or_init(|| Mutex::new(None))
+}
+
+fn get_cached_extension_settings(extension_id: &str) -> Vec<ExtensionSetting> {
+    let mut cache = get_settings_cache().lock().unwrap();
+    let settings = cache.get_or_insert_with(|| get_settings().extensions);
+
+    settings
+        .iter()
+        .filter(|setting| setting.extension_id == extension_id)
+        .cloned()
+        .collect()
+}
+
+fn update_cached_extension_setting(setting: &ExtensionSetting) {
+    if let Some(settings) = get_settings_cache().lock().unwrap().as_mut() {
+        match settings
+            .iter_mut()
+            .find(|s| s.extension_id == setting.extension_id && s.setting_id == setting.setting_id)
+        {
+            Some(current_setting) => *current_setting = setting.to_owned(),
+            None => settings.push(setting.to_owned()),
+        }
+    }
+}
+
+/// Gets the manifest settings that should be shown in the settings, in order.
+///
+/// A setting is shown when it's available in the current os and all its show conditions are met. Settings without a
+/// stored value are compared with their default value.
+pub fn get_visible_settings<'a>(
+    extension: &'a ExtensionManifest,
+    settings: &[ExtensionSetting],
+) -> Vec<&'a ExtensionManifestSetting> {
+    let manifest_settings = extension.settings.as_deref().unwrap_or_default();
+
+    let get_value = |setting_id: &str| {
+        settings
+            .iter()
+            .find(|s| s.extension_id == extension.id && s.setting_id == setting_id)
+            .map(|s| s.setting_value.to_owned())
+            .or_else(|| {
+                manifest_settings
+                    .iter()
+                    .find(|s| s.id == setting_id)
+                    .map(|s| s.default_value.to_owned())
+            })
+    };
+
+    manifest_settings
+        .iter()
+        .filter(|setting| {
+            is_current_os(&setting.os)
+                && setting
+                    .show_conditions
+                    .iter()
+                    .flatten()
+                    .all(|condition| condition.is_met(&get_value))
+        })
+        .collect()
 }
 
+/// Checks if the setting should be shown in the settings. See [`get_visible_settings`].
+pub fn is_setting_visible(
+    extension: &ExtensionManifest,
+    setting_id: impl AsRef<str>,
+    settings: &[ExtensionSetting],
+) -> bool {
+    let setting_id = setting_id.as_ref();
+
+    get_visible_settings(extension, settings)
+        .iter()
+        .any(|setting| setting.id == setting_id)
+}
+
+/// Checks if an os value of a manifest, like `*` or `linux`, includes the current os
+fn is_current_os(os: &str) -> bool {
+    os == "*" || os == env::consts::OS
+}
+
+/// Gets the stored setting, which has typed accessors like [`ExtensionSetting::get_bool`]
+pub fn get_extension_setting_entry(
+    extension_id: impl Into<String>,
+    setting_id: impl Into<String>,
+) -> Option<ExtensionSetting> {
+    let setting_id = setting_id.into();
+    let extension_id = extension_id.into();
+
+    get_settings()
+        .extensions
+        .into_iter()
+        .find(|setting| setting.extension_id == extension_id && setting.setting_id == setting_id)
+}
+
+/// Gets the stored value of the setting. Secret settings aren't stored in the settings, so extensions read them with
+/// [`ExtensionContext::get_secret`].
 pub fn get_extension_setting(
     extension_id: impl Into<String>,
     setting_id: impl Into<String>,
@@ -277,3 +1386,214 @@ pub fn get_extension_setting(
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_manifest(json: &str) -> ExtensionManifest {
+        let mut manifest = serde_json::json!({
+            "id": "weather",
+            "name": "Weather",
+            "description": "Shows the weather",
+            "keyword": "w",
+        });
+
+        let fields: serde_json::Value = serde_json::from_str(json).unwrap();
+        manifest
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().to_owned());
+
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    #[test]
+    fn protocol_1_extensions_use_the_legacy_transport() {
+        let manifest = get_manifest("{}");
+        assert_eq!(manifest.get_transports(), vec![TransportKind::legacy()]);
+    }
+
+    #[test]
+    fn newer_extensions_use_every_supported_transport() {
+        let manifest = get_manifest(r#"{ "protocol": 2 }"#);
+        assert_eq!(manifest.get_transports(), TransportKind::supported());
+
+        let manifest = get_manifest(r#"{ "protocol": 2, "transports": ["File"] }"#);
+        assert_eq!(manifest.get_transports(), vec![TransportKind::File]);
+    }
+
+    fn get_condition(json: &str) -> ExtensionManifestShowCondition {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn get_value(setting_id: &str) -> Option<String> {
+        match setting_id {
+            "units" => Some(String::from("metric")),
+            "provider" => Some(String::from("openweather")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn not_equal_and_one_of_compare_the_setting_value() {
+        let condition = get_condition(
+            r#"{ "setting_id": "units", "setting_value": "imperial", "operator": "NotEqual" }"#,
+        );
+        assert!(condition.is_met(&get_value));
+
+        let condition = get_condition(
+            r#"{ "setting_id": "units", "setting_value": "metric", "operator": "NotEqual" }"#,
+        );
+        assert!(!condition.is_met(&get_value));
+
+        let condition = get_condition(
+            r#"{ "setting_id": "provider", "operator": "OneOf", "setting_values": ["openweather", "metno"] }"#,
+        );
+        assert!(condition.is_met(&get_value));
+
+        let condition = get_condition(
+            r#"{ "setting_id": "provider", "operator": "OneOf", "setting_values": ["metno"] }"#,
+        );
+        assert!(!condition.is_met(&get_value));
+    }
+
+    #[test]
+    fn nested_groups_combine_their_conditions() {
+        let condition = get_condition(
+            r#"{
+                "all": [
+                    { "setting_id": "units", "setting_value": "metric" },
+                    {
+                        "any": [
+                            { "setting_id": "provider", "setting_value": "metno" },
+                            { "setting_id": "provider", "setting_value": "openweather" }
+                        ]
+                    }
+                ]
+            }"#,
+        );
+        assert!(condition.is_met(&get_value));
+
+        let condition = get_condition(
+            r#"{
+                "any": [
+                    { "setting_id": "units", "setting_value": "imperial" },
+                    {
+                        "all": [
+                            { "setting_id": "provider", "setting_value": "openweather" },
+                            { "setting_id": "missing", "setting_value": "value" }
+                        ]
+                    }
+                ]
+            }"#,
+        );
+        assert!(!condition.is_met(&get_value));
+    }
+
+    #[test]
+    fn os_conditions_are_only_met_in_their_os() {
+        let condition = get_condition(&format!(r#"{{ "os": "{}" }}"#, env::consts::OS));
+        assert!(condition.is_met(&get_value));
+
+        let condition = get_condition(r#"{ "os": "plan9" }"#);
+        assert!(!condition.is_met(&get_value));
+
+        // The os must match even when the setting does
+        let condition =
+            get_condition(r#"{ "os": "plan9", "setting_id": "units", "setting_value": "metric" }"#);
+        assert!(!condition.is_met(&get_value));
+    }
+
+    #[test]
+    fn visible_settings_fall_back_to_the_default_values() {
+        let manifest = get_manifest(
+            r#"{
+                "settings": [
+                    {
+                        "id": "units",
+                        "title": "Units",
+                        "description": "The temperature units",
+                        "setting_type": "Input",
+                        "default_value": "metric"
+                    },
+                    {
+                        "id": "decimals",
+                        "title": "Decimals",
+                        "description": "The decimals of the temperature",
+                        "setting_type": "Number",
+                        "default_value": "1",
+                        "show_conditions": [{ "setting_id": "units", "setting_value": "metric" }]
+                    }
+                ]
+            }"#,
+        );
+
+        let get_visible_ids = |settings: &[ExtensionSetting]| {
+            get_visible_settings(&manifest, settings)
+                .iter()
+                .map(|setting| setting.id.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(get_visible_ids(&[]), vec!["units", "decimals"]);
+
+        let settings = vec![ExtensionSetting {
+            extension_id: String::from("weather"),
+            setting_id: String::from("units"),
+            setting_value: String::from("imperial"),
+        }];
+
+        assert_eq!(get_visible_ids(&settings), vec!["units"]);
+    }
+
+    fn get_setting(setting_type: &str, limits: &str) -> ExtensionManifestSetting {
+        serde_json::from_str(&format!(
+            r#"{{
+                "id": "setting",
+                "title": "Setting",
+                "description": "A setting",
+                "setting_type": "{setting_type}",
+                "default_value": ""
+                {limits}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn toggles_are_stored_as_true_or_false() {
+        let setting = get_setting("Toggle", "");
+
+        assert_eq!(setting.validate_value("yes").unwrap(), "true");
+        assert_eq!(setting.validate_value("Off").unwrap(), "false");
+        assert!(setting.validate_value("maybe").is_err());
+    }
+
+    #[test]
+    fn numbers_must_be_inside_the_limits() {
+        let setting = get_setting("Number", r#", "min": 1, "max": 10"#);
+
+        assert_eq!(setting.validate_value(" 3.0 ").unwrap(), "3");
+        assert!(setting.validate_value("0").is_err());
+        assert!(setting.validate_value("11").is_err());
+        assert!(setting.validate_value("NaN").is_err());
+        assert!(setting.validate_value("three").is_err());
+    }
+
+    #[test]
+    fn invalid_colors_and_hotkeys_are_rejected() {
+        let setting = get_setting("Color", "");
+
+        assert_eq!(setting.validate_value("#ffe072").unwrap(), "#FFE072");
+        assert!(setting.validate_value("ffe072").is_err());
+        assert!(setting.validate_value("#ffe07").is_err());
+        assert!(setting.validate_value("#gggggg").is_err());
+
+        let setting = get_setting("Hotkey", "");
+
+        assert_eq!(setting.validate_value("ctrl + k").unwrap(), "ctrl+k");
+        assert!(setting.validate_value("ctrl+").is_err());
+        assert!(setting.validate_value("").is_err());
+    }
+}
@@ -1,4 +1,7 @@
+pub mod error;
 pub mod features;
 pub mod paths;
+pub mod protocol;
 pub mod results;
+pub mod transport;
 pub mod utils;
\ No newline at end of file
@@ -1,6 +1,10 @@
+pub mod error;
 pub mod features;
 pub mod paths;
+pub mod protocol;
 pub mod results;
+pub mod transport;
+pub mod utils;
 
 fn main(){
     
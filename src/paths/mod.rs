@@ -68,6 +68,13 @@ pub fn get_indexing_extensions_path() -> PathBuf {
     path
 }
 
+/// The problems found in the extension manifests the last time they were indexed
+pub fn get_indexing_extension_diagnostics_path() -> PathBuf {
+    let mut path = get_indexing_dir();
+    path.push("extension-diagnostics.bin");
+    path
+}
+
 pub fn get_indexing_apps_path() -> PathBuf {
     let mut path = get_indexing_dir();
     path.push("apps.bin");
@@ -95,12 +102,72 @@ pub fn get_extension_response_path() -> PathBuf {
     path
 }
 
+/// The directory with the files extensions write their results to, named after the request id
+pub fn get_extension_responses_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-responses");
+    path
+}
+
+pub fn get_extension_response_path_for(request_id: impl Into<String>) -> PathBuf {
+    let mut path = get_extension_responses_dir();
+    path.push(format!("{}.jsonl", request_id.into()));
+    path
+}
+
+/// The directory with the sockets extensions send their results through, named after the request id
+pub fn get_extension_sockets_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-sockets");
+    path
+}
+
+pub fn get_extension_socket_path(request_id: impl Into<String>) -> PathBuf {
+    let mut path = get_extension_sockets_dir();
+    path.push(format!("{}.sock", request_id.into()));
+    path
+}
+
 pub fn get_extension_request_path() -> PathBuf {
     let mut path = get_api_dir();
     path.push("extension-request.bin");
     path
 }
 
+/// The directory where each extension request is written to a file named after its id
+pub fn get_extension_requests_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-requests");
+    path
+}
+
+/// The directory where each form response is written to a file named after its request id
+pub fn get_form_responses_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("form-responses");
+    path
+}
+
+/// The directory where the launcher marks requests as cancelled with a file named after their id
+pub fn get_cancelled_requests_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("cancelled-requests");
+    path
+}
+
+/// The directory with the sockets of the extensions running as daemons
+pub fn get_extension_daemons_dir() -> PathBuf {
+    let mut path = get_api_dir();
+    path.push("extension-daemons");
+    path
+}
+
+pub fn get_extension_daemon_socket_path(extension_id: impl Into<String>) -> PathBuf {
+    let mut path = get_extension_daemons_dir();
+    path.push(format!("{}.sock", extension_id.into()));
+    path
+}
+
 pub fn get_search_results_path() -> PathBuf{
     let mut path = get_api_dir();
     path.push("search-results.bin");
@@ -125,21 +192,62 @@ pub fn get_extensions_dir() -> PathBuf {
     path
 }
 
+/// Where extensions are prepared before being moved to the extensions directory.
+///
+/// It's next to the extensions directory so the move is a rename.
+pub fn get_extensions_staging_dir() -> PathBuf {
+    let mut path = get_app_dir();
+    path.push("extensions-staging");
+    path
+}
+
+/// The encrypted secrets of the extension settings, used when the Secret Service isn't available
+pub fn get_secrets_path() -> PathBuf {
+    let mut path = get_app_dir();
+    path.push("secrets.bin");
+    path
+}
+
+/// The key of the secrets file
+pub fn get_secrets_key_path() -> PathBuf {
+    let mut path = get_app_dir();
+    path.push("secrets.key");
+    path
+}
+
+/// The settings file, stored as JSON so it can be edited by hand
 pub fn get_settings_path() -> PathBuf {
     match env::consts::OS {
         "windows" => {
             let mut path = get_app_dir();
-            path.push("settings.bin");
+            path.push("settings.json");
             path
         }
         _ => {
             let mut path = get_home_dir();
-            path.push(".config/whiskers-launcher/settings.bin");
+            path.push(".config/whiskers-launcher/settings.json");
             path
         }
     }
 }
 
+/// Counts the writes of the settings file, so running processes can tell when the settings changed
+pub fn get_settings_generation_path() -> PathBuf {
+    get_settings_path().with_file_name("settings.generation")
+}
+
+/// Where the files of imported settings bundles, like wallpapers and search engine icons, are extracted
+pub fn get_imported_settings_files_dir() -> PathBuf {
+    let mut path = get_app_dir();
+    path.push("settings-files");
+    path
+}
+
+/// The bincode settings file of older versions, migrated to the settings file on the first read
+pub fn get_legacy_settings_path() -> PathBuf {
+    get_settings_path().with_file_name("settings.bin")
+}
+
 pub fn get_autostart_dir() -> PathBuf {
     match env::consts::OS {
         "windows" => {
@@ -152,7 +260,7 @@ pub fn get_autostart_dir() -> PathBuf {
         _ => {
             let mut path = get_home_dir();
             path.push(".config/autostart");
-            return path;
+            path
         }
     }
 }
@@ -0,0 +1,424 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{Error, Result},
+    features::extensions::{ExtensionRequest, ExtensionRequestType, FormResponse, FormResult},
+    results::{
+        OpenFormAction, ResultAction, SearchResult, SearchResults, SearchResultsMessage,
+        SearchViewType,
+    },
+};
+
+/// The protocol version spoken by this version of the crate
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The oldest protocol version this version of the crate can still decode
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// How the launcher writes the messages an extension reads
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum MessageEncoding {
+    /// Bincode with a version header. Compact, but only practical to decode from rust.
+    Binary,
+
+    /// A json [`Envelope`], readable from any language
+    Json,
+}
+
+/// Marks a binary message with a version header. Messages without it are from protocol 1.
+const BINARY_MAGIC: &[u8; 4] = b"WLPV";
+
+/// The json envelope wrapped around every message
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct Envelope<T> {
+    pub protocol: u32,
+    pub payload: T,
+}
+
+/// A message exchanged between the launcher and the extensions.
+///
+/// Messages whose layout changed between versions override the upgrade functions to decode the older layouts.
+pub trait ProtocolMessage: Serialize + DeserializeOwned {
+    /// Decodes a binary payload written with an older protocol version
+    fn upgrade_binary(_version: u32, payload: &[u8]) -> Result<Self> {
+        Ok(bincode::deserialize(payload)?)
+    }
+
+    /// Decodes a json payload written with an older protocol version
+    fn upgrade_json(_version: u32, payload: Value) -> Result<Self> {
+        Ok(serde_json::from_value(payload)?)
+    }
+}
+
+/// Encodes the message with the encoding
+pub fn encode_message<T: ProtocolMessage>(
+    message: &T,
+    encoding: MessageEncoding,
+) -> Result<Vec<u8>> {
+    match encoding {
+        MessageEncoding::Binary => encode_binary(message),
+        MessageEncoding::Json => encode_json(message),
+    }
+}
+
+/// Decodes a message written with any encoding
+///
+/// Binary messages can start like json, so bytes that aren't valid json are also tried as binary. The json error is
+/// returned when that fails too, so problems like an unsupported protocol aren't reported as invalid binary data.
+pub fn decode_message<T: ProtocolMessage>(bytes: &[u8]) -> Result<T> {
+    if bytes.first() != Some(&b'{') {
+        return decode_binary(bytes);
+    }
+
+    let json_error = match decode_json(bytes) {
+        Ok(message) => return Ok(message),
+        Err(error) => error,
+    };
+
+    if serde_json::from_slice::<Value>(bytes).is_ok() {
+        return Err(json_error);
+    }
+
+    decode_binary(bytes).map_err(|_| json_error)
+}
+
+/// Encodes the message with a binary version header
+pub fn encode_binary<T: ProtocolMessage>(message: &T) -> Result<Vec<u8>> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    bytes.extend(PROTOCOL_VERSION.to_le_bytes());
+    bytes.extend(bincode::serialize(message)?);
+
+    Ok(bytes)
+}
+
+/// Decodes a binary message, upgrading it when it was written with an older protocol version
+pub fn decode_binary<T: ProtocolMessage>(bytes: &[u8]) -> Result<T> {
+    if bytes.len() < 8 || &bytes[..4] != BINARY_MAGIC {
+        return T::upgrade_binary(1, bytes);
+    }
+
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    check_protocol_version(version)?;
+
+    let payload = &bytes[8..];
+
+    match version {
+        PROTOCOL_VERSION => Ok(bincode::deserialize(payload)?),
+        _ => T::upgrade_binary(version, payload),
+    }
+}
+
+/// Encodes the message as a json [`Envelope`]
+pub fn encode_json<T: ProtocolMessage>(message: &T) -> Result<Vec<u8>> {
+    let envelope = Envelope {
+        protocol: PROTOCOL_VERSION,
+        payload: message,
+    };
+
+    Ok(serde_json::to_vec(&envelope)?)
+}
+
+/// Decodes a json message, upgrading it when it was written with an older protocol version.
+///
+/// Messages without an envelope are from protocol 1.
+pub fn decode_json<T: ProtocolMessage>(bytes: &[u8]) -> Result<T> {
+    decode_versioned_json(bytes).map(|(_, message)| message)
+}
+
+/// Same as [`decode_json`] but also returns the protocol version the message was written with
+pub fn decode_versioned_json<T: ProtocolMessage>(bytes: &[u8]) -> Result<(u32, T)> {
+    let value: Value = serde_json::from_slice(bytes)?;
+
+    let (version, payload) = match value {
+        Value::Object(mut object)
+            if object.len() == 2
+                && object.contains_key("protocol")
+                && object.contains_key("payload") =>
+        {
+            // Versions that don't fit are never supported, instead of wrapping around to a supported one
+            let version = object["protocol"].as_u64().unwrap_or_default();
+            let version = u32::try_from(version).unwrap_or(u32::MAX);
+            (version, object.remove("payload").unwrap())
+        }
+        value => (1, value),
+    };
+
+    check_protocol_version(version)?;
+
+    let message = match version {
+        PROTOCOL_VERSION => serde_json::from_value(payload)?,
+        _ => T::upgrade_json(version, payload)?,
+    };
+
+    Ok((version, message))
+}
+
+/// Checks if a message written with the protocol version can be decoded
+pub fn check_protocol_version(version: u32) -> Result<()> {
+    if (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedProtocol(version))
+    }
+}
+
+/// [`ExtensionRequest`] as written by protocol 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExtensionRequestV1 {
+    pub extension_id: String,
+    pub request_type: ExtensionRequestType,
+    pub search_text: Option<String>,
+    pub command: Option<String>,
+    pub args: Vec<String>,
+}
+
+/// [`FormResponse`] as written by protocol 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FormResponseV1 {
+    pub results: Vec<FormResult>,
+    pub args: Vec<String>,
+}
+
+/// [`SearchResults`] as written by protocol 1
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchResultsV1 {
+    pub view_type: SearchViewType,
+    pub results: Vec<SearchResult>,
+}
+
+impl ProtocolMessage for ExtensionRequest {
+    fn upgrade_binary(_version: u32, payload: &[u8]) -> Result<Self> {
+        let request: ExtensionRequestV1 = bincode::deserialize(payload)?;
+        Ok(request.into())
+    }
+
+    fn upgrade_json(_version: u32, payload: Value) -> Result<Self> {
+        let request: ExtensionRequestV1 = serde_json::from_value(payload)?;
+        Ok(request.into())
+    }
+}
+
+impl ProtocolMessage for FormResponse {
+    fn upgrade_binary(_version: u32, payload: &[u8]) -> Result<Self> {
+        let response: FormResponseV1 = bincode::deserialize(payload)?;
+        Ok(response.into())
+    }
+}
+
+impl ProtocolMessage for SearchResults {
+    fn upgrade_binary(_version: u32, payload: &[u8]) -> Result<Self> {
+        let results: SearchResultsV1 = bincode::deserialize(payload)?;
+        Ok(results.into())
+    }
+}
+
+impl ProtocolMessage for SearchResultsMessage {
+    /// Protocol 1 extensions send all their results in a single [`SearchResults`]
+    fn upgrade_json(_version: u32, payload: Value) -> Result<Self> {
+        let results: SearchResults = serde_json::from_value(payload)?;
+        Ok(SearchResultsMessage::Batch(results))
+    }
+}
+
+impl ProtocolMessage for OpenFormAction {}
+
+impl ProtocolMessage for ResultAction {}
+
+impl From<ExtensionRequestV1> for ExtensionRequest {
+    fn from(request: ExtensionRequestV1) -> Self {
+        let mut upgraded = ExtensionRequest::new_get_results_request(request.extension_id, "");
+
+        upgraded.request_type = request.request_type;
+        upgraded.search_text = request.search_text;
+        upgraded.command = request.command;
+        upgraded.set_args(request.args)
+    }
+}
+
+impl From<ExtensionRequest> for ExtensionRequestV1 {
+    fn from(request: ExtensionRequest) -> Self {
+        Self {
+            extension_id: request.extension_id,
+            request_type: request.request_type,
+            search_text: request.search_text,
+            command: request.command,
+            args: request.args,
+        }
+    }
+}
+
+impl From<FormResponseV1> for FormResponse {
+    fn from(response: FormResponseV1) -> Self {
+        FormResponse::new(response.results).set_args(response.args)
+    }
+}
+
+impl From<FormResponse> for FormResponseV1 {
+    fn from(response: FormResponse) -> Self {
+        Self {
+            results: response.results,
+            args: response.args,
+        }
+    }
+}
+
+impl From<SearchResultsV1> for SearchResults {
+    fn from(results: SearchResultsV1) -> Self {
+        SearchResults::new_list_results(results.results).set_view_type(results.view_type)
+    }
+}
+
+impl From<SearchResults> for SearchResultsV1 {
+    fn from(results: SearchResults) -> Self {
+        Self {
+            view_type: results.view_type,
+            results: results.results,
+        }
+    }
+}
+
+/// The json schemas of the messages exchanged with extensions and of the extension manifest, by file name
+#[cfg(feature = "json-schema")]
+pub fn get_json_schemas() -> Vec<(&'static str, schemars::Schema)> {
+    use crate::features::extensions::ExtensionManifest;
+    use schemars::schema_for;
+
+    vec![
+        ("extension-manifest.json", schema_for!(ExtensionManifest)),
+        (
+            "extension-request.json",
+            schema_for!(Envelope<ExtensionRequest>),
+        ),
+        ("form-response.json", schema_for!(Envelope<FormResponse>)),
+        (
+            "open-form-action.json",
+            schema_for!(Envelope<OpenFormAction>),
+        ),
+        (
+            "search-results-message.json",
+            schema_for!(Envelope<SearchResultsMessage>),
+        ),
+        ("search-results.json", schema_for!(SearchResults)),
+    ]
+}
+
+/// Writes the json schemas to the directory
+#[cfg(feature = "json-schema")]
+pub fn write_json_schemas(dir: impl AsRef<std::path::Path>) -> Result<()> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    for (name, schema) in get_json_schemas() {
+        let mut path = dir.to_owned();
+        path.push(name);
+
+        std::fs::write(path, serde_json::to_string_pretty(&schema)?)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn get_request() -> ExtensionRequest {
+        ExtensionRequest::new_run_command_request("weather", "open").add_arg("berlin")
+    }
+
+    #[test]
+    fn binary_messages_keep_the_request_id() {
+        let request = get_request();
+        let bytes = encode_binary(&request).unwrap();
+        let decoded: ExtensionRequest = decode_message(&bytes).unwrap();
+
+        assert_eq!(&bytes[..4], BINARY_MAGIC);
+        assert_eq!(decoded.request_id, request.request_id);
+        assert_eq!(decoded.command.as_deref(), Some("open"));
+    }
+
+    #[test]
+    fn json_messages_keep_the_request_id() {
+        let request = get_request();
+        let bytes = encode_json(&request).unwrap();
+        let (version, decoded) = decode_versioned_json::<ExtensionRequest>(&bytes).unwrap();
+
+        assert_eq!(version, PROTOCOL_VERSION);
+        assert_eq!(decoded.request_id, request.request_id);
+        assert_eq!(decoded.args, vec!["berlin"]);
+    }
+
+    #[test]
+    fn protocol_1_binary_requests_are_upgraded() {
+        let bytes = bincode::serialize(&ExtensionRequestV1::from(get_request())).unwrap();
+        let decoded: ExtensionRequest = decode_message(&bytes).unwrap();
+
+        assert!(!decoded.request_id.is_empty());
+        assert_eq!(decoded.extension_id, "weather");
+        assert_eq!(decoded.request_type, ExtensionRequestType::RunCommand);
+        assert_eq!(decoded.args, vec!["berlin"]);
+    }
+
+    #[test]
+    fn protocol_1_json_requests_are_upgraded() {
+        let bytes = serde_json::to_vec(&ExtensionRequestV1::from(get_request())).unwrap();
+        let (version, decoded) = decode_versioned_json::<ExtensionRequest>(&bytes).unwrap();
+
+        assert_eq!(version, 1);
+        assert!(!decoded.request_id.is_empty());
+        assert_eq!(decoded.command.as_deref(), Some("open"));
+    }
+
+    #[test]
+    fn protocol_1_form_responses_are_upgraded() {
+        let response = FormResponse::new(vec![FormResult::new("city", "berlin")]);
+        let bytes = bincode::serialize(&FormResponseV1::from(response)).unwrap();
+        let decoded: FormResponse = decode_message(&bytes).unwrap();
+
+        assert_eq!(decoded.request_id, None);
+        assert_eq!(decoded.get_result("city").unwrap().field_value, "berlin");
+    }
+
+    #[test]
+    fn protocol_1_search_results_are_a_single_batch() {
+        let results = SearchResults::new_list_results(vec![]);
+        let bytes = serde_json::to_vec(&results).unwrap();
+        let (version, message) = decode_versioned_json::<SearchResultsMessage>(&bytes).unwrap();
+
+        assert_eq!(version, 1);
+        assert!(matches!(message, SearchResultsMessage::Batch(_)));
+    }
+
+    #[test]
+    fn unsupported_protocols_are_rejected() {
+        let payload = serde_json::to_value(get_request()).unwrap();
+
+        for protocol in [0, 3, u64::from(u32::MAX) + 3] {
+            let bytes =
+                serde_json::to_vec(&json!({ "protocol": protocol, "payload": payload })).unwrap();
+
+            assert!(matches!(
+                decode_json::<ExtensionRequest>(&bytes),
+                Err(Error::UnsupportedProtocol(_))
+            ));
+
+            assert!(matches!(
+                decode_message::<ExtensionRequest>(&bytes),
+                Err(Error::UnsupportedProtocol(_))
+            ));
+        }
+
+        let mut bytes = encode_binary(&get_request()).unwrap();
+        bytes[4..8].copy_from_slice(&3u32.to_le_bytes());
+
+        assert!(matches!(
+            decode_binary::<ExtensionRequest>(&bytes),
+            Err(Error::UnsupportedProtocol(3))
+        ));
+    }
+}
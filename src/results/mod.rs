@@ -3,18 +3,49 @@ use std::{path::PathBuf, vec};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SearchResults {
     pub view_type: SearchViewType,
     pub results: Vec<SearchResult>,
+    /// The id of the request these results answer
+    #[serde(default)]
+    pub request_id: Option<String>,
 }
 
+/// A message sent by an extension that streams its results
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum SearchResultsMessage {
+    /// Results to append to the ones already sent
+    Batch(SearchResults),
+
+    /// No more results will be sent for the request
+    Done { request_id: Option<String> },
+
+    /// The extension failed and won't send more results
+    Error(ExtensionError),
+}
+
+/// An error reported by an extension, shown by the launcher as an error row
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct ExtensionError {
+    pub message: String,
+    pub details: Option<String>,
+    /// The id of the request that failed
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum SearchViewType {
     Grid,
     List,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct SearchResult {
     pub icon: Option<String>,
     pub icon_tint: Option<String>,
@@ -24,6 +55,7 @@ pub struct SearchResult {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct ResultAction {
     pub action_type: ActionType,
     pub dangerous: bool,
@@ -36,6 +68,7 @@ pub struct ResultAction {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ActionType {
     CopyText,
     CopyImage,
@@ -47,26 +80,31 @@ pub enum ActionType {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct CopyTextAction {
     pub text: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct CopyImageAction {
     pub image_path: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct OpenLinkAction {
     pub link: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct OpenAppAction {
     pub app_id: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct OpenFormAction {
     pub extension_id: String,
     pub command: String,
@@ -77,6 +115,7 @@ pub struct OpenFormAction {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct RunExtensionAction {
     pub extension_id: String,
     pub command: String,
@@ -84,6 +123,7 @@ pub struct RunExtensionAction {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormField {
     pub id: String,
     pub field_type: FormFieldType,
@@ -97,6 +137,7 @@ pub struct FormField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum FormFieldType {
     Input,
     TextArea,
@@ -107,6 +148,7 @@ pub enum FormFieldType {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormInputField {
     pub title: String,
     pub description: String,
@@ -116,12 +158,14 @@ pub struct FormInputField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum FormValidation {
     IsNumber,
     IsNotEmpty,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormTextAreaField {
     pub title: String,
     pub description: String,
@@ -131,6 +175,7 @@ pub struct FormTextAreaField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormToggleField {
     pub title: String,
     pub description: String,
@@ -138,6 +183,7 @@ pub struct FormToggleField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormSelectField {
     pub title: String,
     pub description: String,
@@ -146,12 +192,14 @@ pub struct FormSelectField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormSelectOption {
     pub id: String,
     pub text: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormFilePickerField {
     pub title: String,
     pub description: String,
@@ -161,6 +209,7 @@ pub struct FormFilePickerField {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct FormFolderPickerField {
     pub title: String,
     pub description: String,
@@ -173,6 +222,7 @@ impl SearchResults {
         Self {
             view_type: SearchViewType::Grid,
             results,
+            request_id: None,
         }
     }
 
@@ -180,6 +230,7 @@ impl SearchResults {
         Self {
             view_type: SearchViewType::List,
             results,
+            request_id: None,
         }
     }
 
@@ -192,6 +243,31 @@ impl SearchResults {
         self.results = results;
         self
     }
+
+    pub fn set_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+}
+
+impl ExtensionError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            details: None,
+            request_id: None,
+        }
+    }
+
+    pub fn set_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub fn set_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
 }
 
 impl SearchResult {
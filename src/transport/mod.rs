@@ -0,0 +1,406 @@
+#[cfg(unix)]
+use {
+    crate::paths::get_extension_socket_path,
+    std::os::unix::net::{UnixListener, UnixStream},
+    std::{
+        thread,
+        time::{Duration, Instant},
+    },
+};
+
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{ChildStdout, Command},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    features::extensions::get_current_request_id,
+    paths::{get_extension_response_path, get_extension_response_path_for},
+};
+
+/// Environment variable the launcher uses to tell an extension which transport to answer with
+pub const TRANSPORT_ENV: &str = "WHISKERS_LAUNCHER_TRANSPORT";
+
+/// The channels an extension can use to send messages to the launcher.
+///
+/// Every transport carries the same json messages, one per line.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum TransportKind {
+    /// Messages are printed to the extension stdout
+    Stdout,
+
+    /// Messages are written to a file in the api directory
+    File,
+
+    /// Messages are sent through a unix socket in the api directory
+    Socket,
+}
+
+/// A channel used to exchange messages between the launcher and an extension.
+///
+/// The extension side uses [`Transport::send`] and the launcher side uses [`Transport::receive`].
+pub trait Transport {
+    /// Sends a single message
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+
+    /// Waits for the next message. Returns `None` when the other side has nothing more to send.
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+pub struct StdoutTransport {
+    reader: Option<BufReader<ChildStdout>>,
+}
+
+pub struct FileTransport {
+    path: PathBuf,
+    file: Option<File>,
+    messages: Option<VecDeque<Vec<u8>>>,
+}
+
+#[cfg(unix)]
+pub struct SocketTransport {
+    /// The socket file, removed when the launcher side is dropped
+    path: Option<PathBuf>,
+    listener: Option<UnixListener>,
+    stream: Option<BufReader<UnixStream>>,
+    timeout: Option<Duration>,
+}
+
+impl TransportKind {
+    /// The transport used before transports were negotiated.
+    ///
+    /// **linux** => Stdout
+    ///
+    /// **windows** => File
+    pub fn legacy() -> Self {
+        if cfg!(target_os = "windows") {
+            TransportKind::File
+        } else {
+            TransportKind::Stdout
+        }
+    }
+
+    /// The transports available in the current os, from the most to the least preferred
+    pub fn supported() -> Vec<Self> {
+        if cfg!(unix) {
+            vec![
+                TransportKind::Socket,
+                TransportKind::Stdout,
+                TransportKind::File,
+            ]
+        } else {
+            vec![TransportKind::File, TransportKind::Stdout]
+        }
+    }
+
+    /// The transport requested by the launcher. Falls back to the legacy one when the launcher didn't request any.
+    pub fn from_env() -> Self {
+        match env::var(TRANSPORT_ENV).as_deref() {
+            Ok("Stdout") => TransportKind::Stdout,
+            Ok("File") => TransportKind::File,
+            Ok("Socket") => TransportKind::Socket,
+            _ => TransportKind::legacy(),
+        }
+    }
+
+    /// Tells the extension spawned by the command to answer with this transport
+    pub fn set_env(&self, command: &mut Command) {
+        command.env(TRANSPORT_ENV, format!("{self:?}"));
+    }
+}
+
+/// Picks the transport to talk with an extension that speaks the given transports.
+///
+/// The first supported transport wins, so on linux a unix socket is used whenever the extension allows it.
+pub fn negotiate_transport(extension_transports: &[TransportKind]) -> TransportKind {
+    TransportKind::supported()
+        .into_iter()
+        .find(|kind| extension_transports.contains(kind))
+        .unwrap_or_else(TransportKind::legacy)
+}
+
+/// Opens the extension side of a transport
+pub fn connect_transport(kind: TransportKind) -> io::Result<Box<dyn Transport>> {
+    match kind {
+        TransportKind::Stdout => Ok(Box::new(StdoutTransport::new())),
+        TransportKind::File => Ok(Box::new(FileTransport::new())),
+        #[cfg(unix)]
+        TransportKind::Socket => Ok(Box::new(SocketTransport::connect()?)),
+        #[cfg(not(unix))]
+        TransportKind::Socket => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Socket transport is not supported in this os",
+        )),
+    }
+}
+
+impl StdoutTransport {
+    /// Extension side. Messages are printed to stdout.
+    pub fn new() -> Self {
+        Self { reader: None }
+    }
+
+    /// Launcher side. Messages are read from the extension process stdout.
+    pub fn from_child(stdout: ChildStdout) -> Self {
+        Self {
+            reader: Some(BufReader::new(stdout)),
+        }
+    }
+}
+
+impl Default for StdoutTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for StdoutTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(message)?;
+        stdout.write_all(b"\n")?;
+        stdout.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let reader = self.reader.as_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Stdout transport can only receive from a child process",
+            )
+        })?;
+
+        read_line_message(reader)
+    }
+}
+
+impl FileTransport {
+    /// Extension side. Writes to the file of the current request, or to the shared response file when the launcher
+    /// didn't set the request id.
+    pub fn new() -> Self {
+        match get_current_request_id() {
+            Some(request_id) => Self::for_request(request_id),
+            None => Self::from_path(get_extension_response_path()),
+        }
+    }
+
+    /// Launcher side. Every request has its own file, so concurrent requests and form responses don't overwrite it.
+    pub fn for_request(request_id: impl Into<String>) -> Self {
+        Self::from_path(get_extension_response_path_for(request_id))
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            file: None,
+            messages: None,
+        }
+    }
+}
+
+impl Default for FileTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for FileTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            self.file = Some(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        let file = self.file.as_mut().unwrap();
+        file.write_all(message)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+        file.sync_all()
+    }
+
+    /// Reads the messages in the file. The launcher should only call it after the extension exits.
+    ///
+    /// Files written by older extensions contain a single bincode message and are returned as is.
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        if self.messages.is_none() {
+            let bytes = fs::read(&self.path)?;
+
+            let messages = if bytes.first() == Some(&b'{') {
+                bytes
+                    .split(|byte| *byte == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(|line| line.to_vec())
+                    .collect()
+            } else {
+                VecDeque::from(vec![bytes])
+            };
+
+            self.messages = Some(messages);
+        }
+
+        Ok(self.messages.as_mut().unwrap().pop_front())
+    }
+}
+
+#[cfg(unix)]
+impl SocketTransport {
+    /// Extension side. Connects to the socket the launcher is listening on for the current request.
+    pub fn connect() -> io::Result<Self> {
+        let request_id = get_current_request_id().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "The launcher didn't set the request id",
+            )
+        })?;
+
+        let stream = UnixStream::connect(get_extension_socket_path(request_id))?;
+
+        Ok(Self::from_stream(stream))
+    }
+
+    /// Launcher side. Must be called before spawning the extension so it has something to connect to.
+    ///
+    /// Every request has its own socket, so concurrent requests don't take each other's socket.
+    pub fn listen(request_id: impl Into<String>) -> io::Result<Self> {
+        Self::listen_at(get_extension_socket_path(request_id))
+    }
+
+    pub fn listen_at(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        Ok(Self {
+            path: Some(path.to_owned()),
+            listener: Some(UnixListener::bind(path)?),
+            stream: None,
+            timeout: None,
+        })
+    }
+
+    pub fn from_stream(stream: UnixStream) -> Self {
+        Self {
+            path: None,
+            listener: None,
+            stream: Some(BufReader::new(stream)),
+            timeout: None,
+        }
+    }
+
+    /// Makes waiting for the extension to connect or for its next message fail after the timeout
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        if let Some(stream) = &self.stream {
+            let _ = stream.get_ref().set_read_timeout(Some(timeout));
+        }
+
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn get_stream(&mut self) -> io::Result<&mut BufReader<UnixStream>> {
+        if self.stream.is_none() {
+            let stream = self.accept()?;
+            stream.set_read_timeout(self.timeout)?;
+            self.stream = Some(BufReader::new(stream));
+        }
+
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    fn accept(&self) -> io::Result<UnixStream> {
+        let listener = self.listener.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "Socket is not connected")
+        })?;
+
+        let timeout = match self.timeout {
+            Some(timeout) => timeout,
+            None => return listener.accept().map(|(stream, _)| stream),
+        };
+
+        let deadline = Instant::now() + timeout;
+        listener.set_nonblocking(true)?;
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    return Ok(stream);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "Extension didn't connect in time",
+                        ));
+                    }
+
+                    thread::sleep(Duration::from_millis(5));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for SocketTransport {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Transport for SocketTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        let stream = self.get_stream()?.get_mut();
+        stream.write_all(message)?;
+        stream.write_all(b"\n")?;
+        stream.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        read_line_message(self.get_stream()?)
+    }
+}
+
+fn read_line_message(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    loop {
+        let mut line = Vec::new();
+
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+
+        while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        if !line.is_empty() {
+            return Ok(Some(line));
+        }
+    }
+}
@@ -1,6 +1,22 @@
-use std::env;
+use std::{
+    cmp::Ordering,
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
 
 use notify_rust::Notification;
+use semver::Version;
+
+/// The version of this crate, used to check [`crate::features::extensions::ExtensionManifest::min_core_version`]
+pub const CORE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Makes the command run without a terminal window
 pub const FLAG_NO_WINDOW: u32 = 0x08000000;
@@ -47,11 +63,11 @@ pub fn get_search_query(search_input: impl Into<String>) -> SearchQuery {
 }
 
 pub fn on_windows() -> bool {
-    return env::consts::OS == "windows";
+    env::consts::OS == "windows"
 }
 
 pub fn on_linux() -> bool {
-    return env::consts::OS == "linux";
+    env::consts::OS == "linux"
 }
 
 pub fn on_wayland() -> bool {
@@ -95,3 +111,116 @@ pub fn send_notification(title: impl Into<String>, description: impl Into<String
             .expect("Error sending notification");
     }
 }
+
+/// Parses a semantic version. A leading `v`, like in `v1.2.0`, is allowed.
+pub fn parse_version(version: impl AsRef<str>) -> Option<Version> {
+    let version = version.as_ref().trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+
+    Version::parse(version).ok()
+}
+
+/// Compares two semantic versions. Returns `None` if any of them isn't valid.
+pub fn compare_versions(first: impl AsRef<str>, second: impl AsRef<str>) -> Option<Ordering> {
+    Some(parse_version(first)?.cmp(&parse_version(second)?))
+}
+
+/// Checks if the version is newer than the current one. Invalid versions are never newer.
+pub fn is_newer_version(version: impl AsRef<str>, current_version: impl AsRef<str>) -> bool {
+    compare_versions(version, current_version) == Some(Ordering::Greater)
+}
+
+/// Writes the file through a temporary file in the same directory that replaces it.
+///
+/// The content is synced before the rename, so readers and crashes never leave a partially written file.
+pub fn write_file_atomic(path: impl AsRef<Path>, bytes: impl AsRef<[u8]>) -> io::Result<()> {
+    write_file_atomic_with_mode(path.as_ref(), bytes.as_ref(), 0o644)
+}
+
+/// Like [`write_file_atomic`], but only the current user can read the file
+pub(crate) fn write_private_file_atomic(
+    path: impl AsRef<Path>,
+    bytes: impl AsRef<[u8]>,
+) -> io::Result<()> {
+    write_file_atomic_with_mode(path.as_ref(), bytes.as_ref(), 0o600)
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn write_file_atomic_with_mode(path: &Path, bytes: &[u8], mode: u32) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+        _ => PathBuf::from("."),
+    };
+
+    fs::create_dir_all(&dir)?;
+
+    // Unique between processes and threads, so writers never share a temporary file
+    static WRITE_COUNT: AtomicUsize = AtomicUsize::new(0);
+    let write_id = WRITE_COUNT.fetch_add(1, AtomicOrdering::Relaxed);
+    let temp_path = get_sibling_path(path, &format!("{}-{write_id}.tmp", process::id()), true);
+
+    let mut options = fs::OpenOptions::new();
+    options.create(true).write(true).truncate(true);
+
+    #[cfg(unix)]
+    options.mode(mode);
+
+    let result = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    });
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result?;
+
+    // Makes the rename itself durable
+    #[cfg(unix)]
+    File::open(&dir)?.sync_all()?;
+
+    Ok(())
+}
+
+/// An advisory lock on a file, released when dropped
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+/// Waits for an exclusive lock on the file, so other processes don't write it at the same time.
+///
+/// The lock is taken on a `.lock` file next to it, since the file itself is replaced on [`write_file_atomic`].
+pub fn lock_file(path: impl AsRef<Path>) -> io::Result<FileLock> {
+    let path = path.as_ref();
+
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(get_sibling_path(path, "lock", false))?;
+
+    file.lock()?;
+
+    Ok(FileLock { _file: file })
+}
+
+/// A file next to the path with an extra extension, like `.settings.json.tmp` when hidden
+fn get_sibling_path(path: &Path, extension: &str, hidden: bool) -> PathBuf {
+    let mut file_name = OsString::new();
+
+    if hidden {
+        file_name.push(".");
+    }
+
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(format!(".{extension}"));
+
+    path.with_file_name(file_name)
+}